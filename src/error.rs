@@ -0,0 +1,332 @@
+use std::fmt;
+use std::io;
+
+use crate::{DataType, WordType};
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum SchemaParseError {
+    MissingTableName,
+    EmptyTableName,
+    InvalidColumnFormat,
+    EmptyColumnName,
+    DuplicateColumn(String),
+    UnknownType(String),
+    InvalidIndexFormat,
+    DuplicateIndex(String),
+    UnknownIndexColumn(String),
+    NotIndexableColumn(String),
+    ColumnAfterIndex,
+    MultiplePrimaryKeys,
+    MultipleAutoIncrements,
+    InvalidSequence,
+}
+
+/// Error of any database operation. Positions are byte offsets of the word or the operation in the query.
+#[derive(Debug)]
+pub enum Error {
+    Io {
+        action: &'static str,
+        path: String,
+        source: io::Error,
+    },
+    SchemaParse {
+        path: String,
+        line: usize,
+        kind: SchemaParseError,
+    },
+    CorruptedLog {
+        path: String,
+        detail: String,
+    },
+    CorruptedTable {
+        path: String,
+        detail: String,
+    },
+    CorruptedFile {
+        path: String,
+        detail: String,
+    },
+    UnsupportedVersion {
+        path: String,
+        version: u32,
+    },
+    ExportUnavailable {
+        reason: &'static str,
+    },
+    UnclosedString {
+        pos: usize,
+    },
+    UnknownTable {
+        table: String,
+        pos: usize,
+    },
+    TableExists {
+        table: String,
+        pos: usize,
+    },
+    TableQuarantined {
+        table: String,
+        pos: usize,
+    },
+    DuplicateValue {
+        table: String,
+        column: String,
+        value: WordType,
+        pos: usize,
+    },
+    MultiplePrimaryKeys {
+        table: String,
+        pos: usize,
+    },
+    MultipleAutoIncrements {
+        table: String,
+        pos: usize,
+    },
+    MissingValue {
+        table: String,
+        column: String,
+        pos: usize,
+    },
+    ColumnExists {
+        table: String,
+        column: String,
+        pos: usize,
+    },
+    ColumnReferenced {
+        table: String,
+        column: String,
+        child: String,
+        pos: usize,
+    },
+    InvalidForeignKey {
+        column: String,
+        reason: &'static str,
+        pos: usize,
+    },
+    MissingParent {
+        column: String,
+        parent: String,
+        value: WordType,
+        pos: usize,
+    },
+    RowReferenced {
+        table: String,
+        child: String,
+        value: WordType,
+        pos: usize,
+    },
+    TableReferenced {
+        table: String,
+        child: String,
+        pos: usize,
+    },
+    IndexExists {
+        table: String,
+        index: String,
+        pos: usize,
+    },
+    UnknownIndex {
+        table: String,
+        index: String,
+        pos: usize,
+    },
+    UnknownColumn {
+        table: String,
+        column: String,
+        pos: usize,
+    },
+    TypeMismatch {
+        column: String,
+        expected: DataType,
+        found: DataType,
+        pos: usize,
+    },
+    /// Index of a column which isn't `Int` or `Str`
+    NotIndexable {
+        table: String,
+        column: String,
+        found: DataType,
+        pos: usize,
+    },
+    /// `sum` or `avg` of a column which isn't `Int`, `BigInt` or `Float`
+    NotNumeric {
+        op: &'static str,
+        column: String,
+        found: DataType,
+        pos: usize,
+    },
+    UnexpectedWord {
+        expected: &'static str,
+        found: WordType,
+        pos: usize,
+    },
+    StackUnderflow {
+        op: &'static str,
+        needed: usize,
+        found: usize,
+        pos: usize,
+    },
+    MissingColumns {
+        op: &'static str,
+        pos: usize,
+    },
+    MissingSelect {
+        op: &'static str,
+        pos: usize,
+    },
+    NullNotAllowed {
+        column: String,
+        pos: usize,
+    },
+    StringTooLong {
+        column: String,
+        max_len: u32,
+        pos: usize,
+    },
+    IntegerOverflow {
+        op: &'static str,
+        pos: usize,
+    },
+    InvalidConditions {
+        count: usize,
+        pos: usize,
+    },
+    TransactionStarted {
+        pos: usize,
+    },
+    NoTransaction {
+        op: &'static str,
+        pos: usize,
+    },
+    UnsupportedType {
+        table: String,
+        column: String,
+    },
+}
+
+/// Problem which doesn't fail the operation, but may need attention of the user
+#[derive(Debug, PartialEq, Clone)]
+pub enum Warning {
+    UnusedWords {
+        count: usize,
+    },
+    UnusedConditions {
+        count: usize,
+    },
+    IncompleteTransaction {
+        path: String,
+    },
+}
+
+/// Wraps the error of a filesystem operation, for use with `map_err()`
+pub(crate) fn io_error(action: &'static str, path: &str) -> impl FnOnce(io::Error) -> Error {
+    let path = path.to_string();
+    move |source| Error::Io {action, path, source}
+}
+
+impl fmt::Display for SchemaParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingTableName     => write!(f, "table name not provided"),
+            Self::EmptyTableName       => write!(f, "table name can't be empty"),
+            Self::InvalidColumnFormat  => write!(f, "invalid format for column, expected `<name>:<type>`"),
+            Self::EmptyColumnName      => write!(f, "empty column name"),
+            Self::DuplicateColumn(col) => write!(f, "column with name '{col}' already exists in table scheme"),
+            Self::UnknownType(name)    => write!(f, "unknown column type `{name}`"),
+            Self::InvalidIndexFormat   => write!(f, "invalid format for index, expected `index <name> <column>`"),
+            Self::DuplicateIndex(name) => write!(f, "index with name '{name}' already exists in table scheme"),
+            Self::UnknownIndexColumn(col) => write!(f, "index of unknown column '{col}'"),
+            Self::NotIndexableColumn(col) => write!(f, "index of column '{col}' which isn't Int or Str"),
+            Self::ColumnAfterIndex     => write!(f, "columns must be declared before indexes and the sequence"),
+            Self::MultiplePrimaryKeys  => write!(f, "table can have only one primary key column"),
+            Self::MultipleAutoIncrements => write!(f, "table can have only one auto-increment column"),
+            Self::InvalidSequence      => write!(f, "invalid format for sequence, expected `sequence <value>` once"),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io {action, path, source} => write!(f, "unable to {action} {path}: {source}"),
+            Self::SchemaParse {path, line, kind} => write!(f, "{path}:{line}: {kind}"),
+            Self::CorruptedLog {path, detail} => write!(f, "corrupted log file {path}: {detail}"),
+            Self::CorruptedTable {path, detail} => write!(f, "corrupted table file {path}: {detail}"),
+            Self::CorruptedFile {path, detail} => write!(f, "corrupted database file {path}: {detail}"),
+            Self::UnsupportedVersion {path, version} => write!(f, "file {path} has unsupported format version {version}"),
+            Self::ExportUnavailable {reason} => write!(f, "unable to export the database while {reason}"),
+            Self::UnclosedString {pos} => write!(f, "unclosed string literal at position {pos}"),
+            Self::UnknownTable {table, pos} => write!(f, "no such table `{table}` at position {pos}"),
+            Self::TableExists {table, pos} => write!(f, "table `{table}` already exists at position {pos}"),
+            Self::TableQuarantined {table, pos} => write!(f, "table `{table}` at position {pos} is quarantined because of damaged files"),
+            Self::DuplicateValue {table, column, value, pos} => {
+                write!(f, "value {value:?} at position {pos} already exists in column `{column}` of table `{table}`")
+            },
+            Self::MultiplePrimaryKeys {table, pos} => write!(f, "table `{table}` at position {pos} has more than one primary key column"),
+            Self::MultipleAutoIncrements {table, pos} => write!(f, "table `{table}` at position {pos} has more than one auto-increment column"),
+            Self::MissingValue {table, column, pos} => {
+                write!(f, "column `{column}` of table `{table}` has no default value and needs a value at position {pos}")
+            },
+            Self::ColumnExists {table, column, pos} => write!(f, "column `{column}` already exists in table `{table}` at position {pos}"),
+            Self::ColumnReferenced {table, column, child, pos} => {
+                write!(f, "column `{column}` of table `{table}` at position {pos} is referenced by table `{child}`")
+            },
+            Self::InvalidForeignKey {column, reason, pos} => write!(f, "invalid foreign key of column `{column}` at position {pos}: {reason}"),
+            Self::MissingParent {column, parent, value, pos} => {
+                write!(f, "value {value:?} of column `{column}` at position {pos} doesn't exist in parent table `{parent}`")
+            },
+            Self::RowReferenced {table, child, value, pos} => {
+                write!(f, "row of table `{table}` is referenced by value {value:?} of table `{child}`, operation at position {pos}")
+            },
+            Self::TableReferenced {table, child, pos} => write!(f, "table `{table}` at position {pos} is referenced by table `{child}`"),
+            Self::IndexExists {table, index, pos} => write!(f, "index `{index}` of table `{table}` already exists at position {pos}"),
+            Self::UnknownIndex {table, index, pos} => write!(f, "no such index `{index}` in table `{table}` at position {pos}"),
+            Self::UnknownColumn {table, column, pos} => write!(f, "no such column `{column}` in table `{table}` at position {pos}"),
+            Self::TypeMismatch {column, expected, found, pos} => {
+                write!(f, "column `{column}` expected type {expected:?} but found type {found:?} at position {pos}")
+            },
+            Self::NotIndexable {table, column, found, pos} => {
+                write!(f, "column `{column}` of table `{table}` at position {pos} has type {found:?}, only Int and Str columns can be indexed")
+            },
+            Self::NotNumeric {op, column, found, pos} => {
+                write!(f, "`{op}` expected a numeric column but column `{column}` has type {found:?} at position {pos}")
+            },
+            Self::UnexpectedWord {expected, found, pos} => write!(f, "expected {expected} but found {found:?} at position {pos}"),
+            Self::StackUnderflow {op, needed, found, pos} => {
+                write!(f, "not enough arguments for `{op}` operation at position {pos}, provided {found} but needed {needed}")
+            },
+            Self::MissingColumns {op, pos} => write!(f, "`{op}` operation at position {pos} need at least one column"),
+            Self::MissingSelect {op, pos} => write!(f, "`{op}` operation at position {pos} must be used after `select`"),
+            Self::NullNotAllowed {column, pos} => write!(f, "column `{column}` can't be NULL at position {pos}"),
+            Self::StringTooLong {column, max_len, pos} => {
+                write!(f, "string at position {pos} is longer than {max_len} characters allowed in column `{column}`")
+            },
+            Self::IntegerOverflow {op, pos} => write!(f, "result of `{op}` operation at position {pos} doesn't fit into Int"),
+            Self::InvalidConditions {count, pos} => {
+                write!(f, "conditions for operation at position {pos} expect to produce one value, but produce {count}")
+            },
+            Self::TransactionStarted {pos} => write!(f, "transaction is already started at position {pos}"),
+            Self::NoTransaction {op, pos} => write!(f, "no transaction to {op} at position {pos}"),
+            Self::UnsupportedType {table, column} => write!(f, "values of column `{column}` of table `{table}` can't be stored"),
+        }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnusedWords {count} => write!(f, "{count} unused words in the words stack"),
+            Self::UnusedConditions {count} => write!(f, "{count} unused conditions in the conditions stack"),
+            Self::IncompleteTransaction {path} => write!(f, "discarded incomplete transaction at the end of the log file {path}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io {source, ..} => Some(source),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,3947 @@
+use std::io::{BufWriter, Write};
+use std::io::Read;
+use std::fs::File;
+use std::fs;
+use std::cmp::Ordering;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::Path;
+use std::rc::Rc;
+
+mod error;
+mod wal;
+mod timestamp;
+mod single_file;
+mod pager;
+mod stored;
+#[cfg(test)]
+mod tests;
+
+pub use error::{Error, SchemaParseError, Warning};
+use error::io_error;
+use wal::LogRecord;
+use pager::{Layout, PagePool, Pager};
+use stored::StoredRows;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Col {
+    name: String,
+    data_type: DataType,
+    nullable: bool,
+    max_len: Option<u32>,
+    constraint: Option<Constraint>,
+    foreign_key: Option<ForeignKey>,
+    auto_increment: bool,
+    default: Option<WordType>,
+}
+
+impl Col {
+    fn new(name: String, col_type: ColType) -> Self {
+        Self {
+            name,
+            data_type: col_type.data_type,
+            nullable: col_type.nullable,
+            max_len: col_type.max_len,
+            constraint: col_type.constraint,
+            foreign_key: col_type.foreign_key.map(|foreign_key| *foreign_key),
+            auto_increment: col_type.auto_increment,
+            default: col_type.default.map(|default| *default),
+        }
+    }
+
+    /// Column without constraints and options
+    fn plain(name: String, data_type: DataType, nullable: bool) -> Self {
+        Self {
+            name,
+            data_type,
+            nullable,
+            max_len: None,
+            constraint: None,
+            foreign_key: None,
+            auto_increment: false,
+            default: None,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn data_type(&self) -> DataType {
+        self.data_type
+    }
+
+    pub fn nullable(&self) -> bool {
+        self.nullable
+    }
+
+    /// Maximum number of characters in a `Str` column
+    pub fn max_len(&self) -> Option<u32> {
+        self.max_len
+    }
+
+    pub fn constraint(&self) -> Option<Constraint> {
+        self.constraint
+    }
+
+    pub fn foreign_key(&self) -> Option<&ForeignKey> {
+        self.foreign_key.as_ref()
+    }
+
+    /// Whether an `Int` column takes the next value of the table sequence when it's omitted
+    pub fn auto_increment(&self) -> bool {
+        self.auto_increment
+    }
+
+    /// Value of the column when it's omitted in `insert-named`
+    pub fn default(&self) -> Option<&WordType> {
+        self.default.as_ref()
+    }
+
+    fn col_type(&self) -> ColType {
+        ColType {
+            data_type: self.data_type,
+            nullable: self.nullable,
+            max_len: self.max_len,
+            constraint: self.constraint,
+            foreign_key: self.foreign_key.clone().map(Box::new),
+            auto_increment: self.auto_increment,
+            default: self.default.clone().map(Box::new),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct TableSchema {
+    name: String,
+    cols: Vec<Col>,
+    indexes: Vec<Index>,
+    /// Next value of the auto-increment column. It only grows, so values of deleted rows
+    /// aren't reused.
+    sequence: i64,
+}
+
+/// B-tree index of a table column, maps values of the column to positions of rows with them.
+/// NULL isn't indexed. The schema file keeps only the definition of the index, its tree is
+/// built when the table is loaded, from rows in memory or by a scan of the pages of the table.
+#[derive(Debug, Clone)]
+struct Index {
+    name: String,
+    col: usize,
+    tree: BTreeMap<WordType, Vec<usize>>,
+}
+
+/// Indexes with the same definition are equal, their trees are derived from the rows
+impl PartialEq for Index {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.col == other.col
+    }
+}
+
+impl Index {
+    fn new(name: String, col: usize) -> Self {
+        Self {name, col, tree: BTreeMap::new()}
+    }
+
+    fn build(&mut self, rows: &[Row]) {
+        self.tree.clear();
+        for (i, row) in rows.iter().enumerate() {
+            self.insert(i, &row[self.col]);
+        }
+    }
+
+    fn insert(&mut self, row: usize, value: &WordType) {
+        if *value == WordType::Null {
+            return;
+        }
+        let rows = self.tree.entry(value.clone()).or_default();
+        let (Ok(i) | Err(i)) = rows.binary_search(&row);
+        rows.insert(i, row);
+    }
+
+    fn remove(&mut self, row: usize, value: &WordType) {
+        if let Some(rows) = self.tree.get_mut(value) {
+            rows.retain(|i| *i != row);
+            if rows.is_empty() {
+                self.tree.remove(value);
+            }
+        }
+    }
+
+    /// Inserts rows at their positions and moves positions of the rows after them, `inserted` is sorted
+    fn insert_rows(&mut self, inserted: &[(usize, Row)]) {
+        for rows in self.tree.values_mut() {
+            for row in rows.iter_mut() {
+                for (position, _) in inserted {
+                    if position <= row {
+                        *row += 1;
+                    }
+                }
+            }
+        }
+        for (position, row) in inserted {
+            self.insert(*position, &row[self.col]);
+        }
+    }
+
+    /// Removes deleted rows and moves positions of the rows after them, `deleted` is sorted
+    fn remove_rows(&mut self, deleted: &[usize]) {
+        self.tree.retain(|_, rows| {
+            rows.retain(|row| deleted.binary_search(row).is_err());
+            for row in rows.iter_mut() {
+                *row -= deleted.partition_point(|deleted| deleted < row);
+            }
+            !rows.is_empty()
+        });
+    }
+
+    /// Sorted positions of rows matching the comparison with `value`
+    fn lookup(&self, op: &Op, value: &WordType) -> Vec<usize> {
+        let mut rows = match op {
+            Op::Equal => self.tree.get(value).cloned().unwrap_or_default(),
+            Op::Less => self.tree.range(..=value).flat_map(|(_, rows)| rows).copied().collect(),
+            Op::More => self.tree.range(value..).flat_map(|(_, rows)| rows).copied().collect(),
+            _ => unreachable!(),
+        };
+        rows.sort_unstable();
+        rows
+    }
+}
+
+fn rebuild_indexes(table: &mut Table) {
+    for index in &mut table.schema.indexes {
+        index.build(&table.rows);
+    }
+}
+
+pub type Row = Vec<WordType>;
+
+const TEMP_FILE_SUFFIX: &str = ".tmp";
+const CHECKPOINT_FILE: &str = "checkpoint";
+const CHECKPOINT_LOG_SIZE: u64 = 1024 * 1024;
+const TABLE_MAGIC: [u8; 8] = *b"\x89RDB\r\n\x1a\n";
+const TABLE_FORMAT_VERSION: u32 = 1;
+const TABLE_HEADER_LEN: usize = TABLE_MAGIC.len() + 16;
+/// Size of a string in legacy table files without the header
+const LEGACY_STR_LEN: usize = 50;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Table {
+    schema: TableSchema,
+    rows: Vec<Row>,
+}
+
+impl Table {
+    pub fn name(&self) -> &str {
+        &self.schema.name
+    }
+
+    pub fn columns(&self) -> &[Col] {
+        &self.schema.cols
+    }
+
+    pub fn column_idx(&self, name: &str) -> Option<usize> {
+        self.schema.cols.iter().position(|col| col.name == name)
+    }
+
+    pub fn rows(&self) -> &[Row] {
+        &self.rows
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Value in the column `col` of the row number `row`
+    pub fn get(&self, row: usize, col: &str) -> Option<&WordType> {
+        self.rows.get(row)?.get(self.column_idx(col)?)
+    }
+
+    pub fn get_int(&self, row: usize, col: &str) -> Option<i32> {
+        match self.get(row, col)? {
+            WordType::Int(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn get_str(&self, row: usize, col: &str) -> Option<&str> {
+        match self.get(row, col)? {
+            WordType::Str(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn get_bool(&self, row: usize, col: &str) -> Option<bool> {
+        match self.get(row, col)? {
+            WordType::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn get_float(&self, row: usize, col: &str) -> Option<f64> {
+        match self.get(row, col)? {
+            WordType::Float(Float(value)) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn get_bigint(&self, row: usize, col: &str) -> Option<i64> {
+        match self.get(row, col)? {
+            WordType::BigInt(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Seconds since 1970-01-01T00:00:00 UTC
+    pub fn get_timestamp(&self, row: usize, col: &str) -> Option<i64> {
+        match self.get(row, col)? {
+            WordType::Timestamp(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn get_bytes(&self, row: usize, col: &str) -> Option<&[u8]> {
+        match self.get(row, col)? {
+            WordType::Bytes(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        assert!(DataType::Count as u8 == 9, "Exhaustive DataType handling in Table::fmt()");
+        // Long column names like names of aggregates widen their columns
+        let mut widths = vec![];
+        for Col {name, data_type, ..} in &self.schema.cols {
+            let width = match data_type {
+                DataType::Int       => 5,
+                DataType::Str       => 20,
+                DataType::Type      => 5,
+                DataType::Bool      => 6,
+                DataType::Float     => 20,
+                DataType::BigInt    => 20,
+                DataType::Timestamp => 20,
+                DataType::Bytes     => 20,
+                _                   => unreachable!(),
+            };
+            widths.push(width.max(name.len() + 1));
+            write!(f, "{name:>width$}", width = widths[widths.len() - 1])?;
+        }
+        writeln!(f)?;
+        for row in &self.rows {
+            for (word, width) in row.iter().zip(&widths) {
+                match word {
+                    WordType::Int(value)       => write!(f, "{value:>width$}")?,
+                    WordType::Str(value)       => write!(f, "{value:>width$}")?,
+                    WordType::Bool(value)      => write!(f, "{value:>width$}")?,
+                    WordType::Float(value)     => write!(f, "{:>width$}", value.to_string())?,
+                    WordType::BigInt(value)    => write!(f, "{value:>width$}")?,
+                    WordType::Timestamp(value) => write!(f, "{:>width$}", timestamp::format(*value))?,
+                    WordType::Bytes(value)     => write!(f, "{:>width$}", bytes_to_string(value))?,
+                    WordType::Null             => write!(f, "{:>width$}", "NULL")?,
+                    _                          => unreachable!(),
+                }
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Layout of the database files on disk
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// Directory with a schema file and a table file of every table
+    Directory,
+    /// One file with schemas and table files of all tables stored in pages
+    SingleFile,
+}
+
+impl Format {
+    fn log_path(self, path: &str) -> String {
+        match self {
+            Format::Directory => wal::log_path(path),
+            Format::SingleFile => format!("{path}-wal"),
+        }
+    }
+
+    fn checkpoint_path(self, path: &str) -> String {
+        match self {
+            Format::Directory => format!("{path}/{CHECKPOINT_FILE}"),
+            Format::SingleFile => format!("{path}-{CHECKPOINT_FILE}"),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Database {
+    path: String,
+    format: Format,
+    paged: PagedTables,
+    transaction: Option<Transaction>,
+    tables: Vec<Table>,
+    quarantined: Vec<QuarantinedTable>,
+    /// Warnings not yet taken by `Connection::take_warnings()`
+    warnings: Vec<Warning>,
+}
+
+/// Stored tables keep their rows in pages of their table files. Rows of such a table are empty
+/// in `Database::tables`, queries read them page by page and changes load only the changed pages.
+#[derive(Debug)]
+struct PagedTables {
+    pool: PagePool,
+    /// Rows of tables by their names, tables which live in memory aren't here
+    tables: HashMap<String, StoredRows>,
+}
+
+impl PagedTables {
+    fn new() -> Self {
+        Self {pool: PagePool::new(pager::POOL_PAGES), tables: HashMap::new()}
+    }
+}
+
+impl Database {
+    fn is_quarantined(&self, table: &str) -> bool {
+        self.quarantined.iter().any(|quarantined| quarantined.name == table)
+    }
+}
+
+/// Table left out of the database because its files can't be read
+#[derive(Debug)]
+pub struct QuarantinedTable {
+    name: String,
+    error: Error,
+}
+
+impl QuarantinedTable {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn error(&self) -> &Error {
+        &self.error
+    }
+}
+
+/// Changes made since the start of the transaction. Every query runs in an implicit
+/// transaction committed at the end of the query, unless `begin` starts an explicit one.
+#[derive(Debug)]
+struct Transaction {
+    explicit: bool,
+    records: Vec<LogRecord>,
+    undo: Vec<Undo>,
+}
+
+impl Transaction {
+    fn new(explicit: bool) -> Self {
+        Self {
+            explicit,
+            records: vec![],
+            undo: vec![],
+        }
+    }
+}
+
+/// Inverse of an applied `LogRecord`
+#[derive(Debug)]
+enum Undo {
+    RemoveLastRow {
+        table: String,
+    },
+    InsertRows {
+        table: String,
+        rows: Vec<(usize, Row)>,
+    },
+    SetRows {
+        table: String,
+        rows: Vec<(usize, Row)>,
+    },
+    RemoveTable {
+        table: String,
+    },
+    InsertTable {
+        idx: usize,
+        table: Table,
+        stored: Option<StoredRows>,
+    },
+    SetForeignKey {
+        table: String,
+        col: usize,
+        foreign_key: Option<ForeignKey>,
+    },
+    RemoveLastColumn {
+        table: String,
+        schema: TableSchema,
+    },
+    InsertColumn {
+        table: String,
+        schema: TableSchema,
+        col: usize,
+        values: Vec<WordType>,
+    },
+    RenameColumn {
+        table: String,
+        col: usize,
+        name: String,
+    },
+    RenameTable {
+        table: String,
+        name: String,
+    },
+    RemoveIndex {
+        table: String,
+        name: String,
+    },
+    InsertIndex {
+        table: String,
+        idx: usize,
+        index: Index,
+    },
+}
+
+#[repr(u8)]
+#[derive(Debug, PartialEq, Clone)]
+enum Op {
+    PushWord {
+        data_type: DataType, 
+        word_type: WordType
+    },
+    Select,
+    Insert,
+    Delete,
+    Or,
+    And,
+    Equal,
+    NotEqual,
+    Less,
+    More,
+    Create,
+    Drop,
+    Update,
+    Begin,
+    Commit,
+    Rollback,
+    OrderBy,
+    Limit,
+    Offset,
+    Aggregate(Aggregate),
+    GroupBy,
+    Having,
+    Join,
+    LeftJoin,
+    IsNull,
+    IsNotNull,
+    CreateIndex,
+    DropIndex,
+    InsertNamed,
+    AddColumn,
+    DropColumn,
+    RenameColumn,
+    Rename,
+    Tables,
+    Describe,
+    Info,
+    Count,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Aggregate {
+    Count,
+    Sum,
+    Min,
+    Max,
+    Avg,
+}
+
+impl Aggregate {
+    fn name(&self) -> &'static str {
+        match self {
+            Aggregate::Count => "count",
+            Aggregate::Sum   => "sum",
+            Aggregate::Min   => "min",
+            Aggregate::Max   => "max",
+            Aggregate::Avg   => "avg",
+        }
+    }
+}
+
+impl Op {
+    fn as_u8(&self) -> u8 {
+        unsafe { *<*const _>::from(self).cast::<u8>() }
+    }
+
+    fn name(&self) -> &'static str {
+        assert!(Op::Count.as_u8() == 36, "Exhaustive Op handling in Op::name()");
+        match self {
+            Op::PushWord {..} => "push",
+            Op::Select        => "select",
+            Op::Insert        => "insert",
+            Op::Delete        => "delete",
+            Op::Or            => "or",
+            Op::And           => "and",
+            Op::Equal         => "==",
+            Op::NotEqual      => "!=",
+            Op::Less          => "<",
+            Op::More          => ">",
+            Op::Create        => "create",
+            Op::Drop          => "drop",
+            Op::Update        => "update",
+            Op::Begin         => "begin",
+            Op::Commit        => "commit",
+            Op::Rollback      => "rollback",
+            Op::OrderBy       => "order-by",
+            Op::Limit         => "limit",
+            Op::Offset        => "offset",
+            Op::Aggregate(aggregate) => aggregate.name(),
+            Op::GroupBy       => "group-by",
+            Op::Having        => "having",
+            Op::Join          => "join",
+            Op::LeftJoin      => "left-join",
+            Op::IsNull        => "is-null",
+            Op::IsNotNull     => "is-not-null",
+            Op::CreateIndex   => "create-index",
+            Op::DropIndex     => "drop-index",
+            Op::InsertNamed   => "insert-named",
+            Op::AddColumn     => "add-column",
+            Op::DropColumn    => "drop-column",
+            Op::RenameColumn  => "rename-column",
+            Op::Rename        => "rename",
+            Op::Tables        => "tables",
+            Op::Describe      => "describe",
+            Op::Info          => "info",
+            Op::Count         => unreachable!(),
+        }
+    }
+}
+
+/// Operation and its position in the query
+#[derive(Debug, PartialEq, Clone)]
+struct Token {
+    op: Op,
+    pos: usize,
+}
+
+/// Value in the words stack and its position in the query
+#[derive(Debug, PartialEq, Clone)]
+struct Word {
+    data_type: DataType,
+    value: WordType,
+    pos: usize,
+}
+
+// TODO: Introduce a sized string type
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Clone)]
+pub enum WordType {
+    Int(i32),
+    Str(String),
+    /// Column type in `create`
+    Type(ColType),
+    Null,
+    Bool(bool),
+    Float(Float),
+    BigInt(i64),
+    /// Seconds since 1970-01-01T00:00:00 UTC
+    Timestamp(i64),
+    Bytes(Vec<u8>),
+}
+
+/// Value of a `Float` column. Unlike `f64` it is equal to itself and can be a key of
+/// a group or a join, values are compared bit by bit with `f64::total_cmp()`.
+#[derive(Debug, Clone, Copy)]
+pub struct Float(pub f64);
+
+impl PartialEq for Float {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Float {}
+
+impl PartialOrd for Float {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Float {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+impl std::hash::Hash for Float {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.0.to_bits().hash(state);
+    }
+}
+
+/// Floats are printed with the decimal point, so they can be read back as `Float` literals
+impl fmt::Display for Float {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.0)
+    }
+}
+
+fn bytes_to_string(bytes: &[u8]) -> String {
+    let mut content = "0x".to_string();
+    for byte in bytes {
+        content.push_str(&format!("{byte:02x}"));
+    }
+    content
+}
+
+/// Parses a `Bytes` literal, an even number of hex digits after `0x`
+fn try_parse_bytes(word: &str) -> Option<Vec<u8>> {
+    let digits = word.strip_prefix("0x")?;
+    if digits.len() % 2 != 0 || !digits.bytes().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..digits.len()).step_by(2).map(|i| u8::from_str_radix(&digits[i..i + 2], 16).ok()).collect()
+}
+
+/// Parses a `Float` literal, digits with a decimal point like `2.5` or `-0.75`
+fn try_parse_float(word: &str) -> Option<f64> {
+    let digits = word.strip_prefix('-').unwrap_or(word);
+    let (int, fraction) = digits.split_once('.')?;
+    if int.is_empty() || fraction.is_empty() || !int.bytes().chain(fraction.bytes()).all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    word.parse().ok()
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Clone, Copy)]
+pub enum DataType {
+    Int,
+    Str,
+    Type,
+    Bool,
+    Float,
+    BigInt,
+    Timestamp,
+    Bytes,
+    /// Type of the `null` literal, columns can't have it
+    Null,
+    Count,
+}
+
+fn try_parse_data_type(col_type: &str) -> Option<DataType> {
+    assert_eq!(DataType::Count as u8, 9);
+    match col_type {
+        "Int"  => Some(DataType::Int),
+        "Str"  => Some(DataType::Str),
+        "Type" => Some(DataType::Type),
+        "Bool"      => Some(DataType::Bool),
+        "Float"     => Some(DataType::Float),
+        "BigInt"    => Some(DataType::BigInt),
+        "Timestamp" => Some(DataType::Timestamp),
+        "Bytes"     => Some(DataType::Bytes),
+        _      => None,
+    }
+} 
+
+/// Type of a column declared in a schema file or in `create`
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Clone)]
+pub struct ColType {
+    data_type: DataType,
+    nullable: bool,
+    max_len: Option<u32>,
+    constraint: Option<Constraint>,
+    // Boxed to keep words small
+    foreign_key: Option<Box<ForeignKey>>,
+    auto_increment: bool,
+    default: Option<Box<WordType>>,
+}
+
+/// Values of a primary key column are unique and not NULL, values of a unique column
+/// are unique, but any number of rows can have NULL in it
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Clone, Copy)]
+pub enum Constraint {
+    PrimaryKey,
+    Unique,
+}
+
+/// Value of a child column referencing a primary key or unique column of the parent table.
+/// Every value of the child column which isn't NULL must exist in the parent column.
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Clone)]
+pub struct ForeignKey {
+    table: String,
+    col: String,
+    on_delete: OnDelete,
+}
+
+impl ForeignKey {
+    pub fn table(&self) -> &str {
+        &self.table
+    }
+
+    pub fn col(&self) -> &str {
+        &self.col
+    }
+
+    pub fn on_delete(&self) -> OnDelete {
+        self.on_delete
+    }
+}
+
+/// What happens to child rows when their parent row is deleted
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Clone, Copy)]
+pub enum OnDelete {
+    /// Deletion of a referenced parent row fails
+    Restrict,
+    /// Child rows are deleted with the parent row
+    Cascade,
+}
+
+/// Parses a column type like `Int`, `Str(20)?` or `Int+primary` with options after `+`
+fn try_parse_col_type(col_type: &str) -> Option<ColType> {
+    let (col_type, mut options) = match col_type.split_once('+') {
+        Some((col_type, options)) => (col_type, Some(options)),
+        None => (col_type, None),
+    };
+    let (col_type, nullable) = match col_type.strip_suffix('?') {
+        Some(col_type) => (col_type, true),
+        None => (col_type, false),
+    };
+    let (data_type, max_len) = match col_type.strip_suffix(')').and_then(|col_type| col_type.split_once('(')) {
+        Some(("Str", max_len)) => (DataType::Str, Some(max_len.parse::<u32>().ok()?)),
+        Some(_) => return None,
+        None => (try_parse_data_type(col_type)?, None),
+    };
+
+    let mut constraint = None;
+    let mut foreign_key = None;
+    let mut auto_increment = false;
+    let mut default = None;
+    while let Some(rest) = options {
+        let (option, next) = match rest.strip_prefix("default(\"") {
+            Some(quoted) => {
+                let end = rest.len() - quoted.len() + quoted.find("\")")? + 2;
+                match &rest[end..] {
+                    "" => (&rest[..end], None),
+                    next => (&rest[..end], Some(next.strip_prefix('+')?)),
+                }
+            },
+            None => match rest.split_once('+') {
+                Some((option, next)) => (option, Some(next)),
+                None => (rest, None),
+            },
+        };
+        options = next;
+        match option {
+            "primary" if constraint.is_none() => constraint = Some(Constraint::PrimaryKey),
+            "unique" if constraint.is_none() => constraint = Some(Constraint::Unique),
+            "auto" if !auto_increment && data_type == DataType::Int && !nullable => auto_increment = true,
+            _ if option.starts_with("default(") && default.is_none() => {
+                let literal = option.strip_prefix("default(")?.strip_suffix(')')?;
+                let literal = match literal.strip_prefix('"') {
+                    Some(quoted) => quoted.strip_suffix('"')?,
+                    None => literal,
+                };
+                default = Some(Box::new(try_parse_value(literal, data_type, nullable, max_len)?));
+            },
+            _ if foreign_key.is_none() => foreign_key = Some(Box::new(try_parse_foreign_key(option)?)),
+            _ => return None,
+        }
+    }
+    if (nullable && constraint == Some(Constraint::PrimaryKey)) || (auto_increment && default.is_some()) {
+        return None;
+    }
+    Some(ColType {data_type, nullable, max_len, constraint, foreign_key, auto_increment, default})
+}
+
+/// Parses a literal of a value of the column type, `null` is a value of a nullable column
+fn try_parse_value(literal: &str, data_type: DataType, nullable: bool, max_len: Option<u32>) -> Option<WordType> {
+    if literal == "null" {
+        return nullable.then_some(WordType::Null);
+    }
+    match data_type {
+        DataType::Int       => literal.parse().ok().map(WordType::Int),
+        DataType::Str if max_len.is_some_and(|max_len| literal.chars().count() > max_len as usize) => None,
+        DataType::Str       => Some(WordType::Str(literal.to_string())),
+        DataType::Bool      => literal.parse().ok().map(WordType::Bool),
+        DataType::Float     => try_parse_float(literal).map(|value| WordType::Float(Float(value))),
+        DataType::BigInt    => literal.strip_suffix('L')?.parse().ok().map(WordType::BigInt),
+        DataType::Timestamp => timestamp::parse(literal).map(WordType::Timestamp),
+        DataType::Bytes     => try_parse_bytes(literal).map(WordType::Bytes),
+        _                   => None,
+    }
+}
+
+/// Literal of a value, the inverse of `try_parse_value()`
+fn value_to_literal(value: &WordType) -> String {
+    match value {
+        WordType::Int(value)       => value.to_string(),
+        WordType::Str(value)       => value.clone(),
+        WordType::Bool(value)      => value.to_string(),
+        WordType::Float(value)     => value.to_string(),
+        WordType::BigInt(value)    => format!("{value}L"),
+        WordType::Timestamp(value) => timestamp::format(*value),
+        WordType::Bytes(value)     => bytes_to_string(value),
+        WordType::Null             => "null".to_string(),
+        WordType::Type(_)          => unreachable!(),
+    }
+}
+
+/// Parses the foreign key option `references(<table>.<column>)` with an optional `,cascade`
+fn try_parse_foreign_key(option: &str) -> Option<ForeignKey> {
+    let reference = option.strip_prefix("references(")?.strip_suffix(')')?;
+    let (reference, on_delete) = match reference.split_once(',') {
+        Some((reference, "cascade")) => (reference, OnDelete::Cascade),
+        Some(_) => return None,
+        None => (reference, OnDelete::Restrict),
+    };
+    let (table, col) = reference.split_once('.')?;
+    if table.is_empty() || col.is_empty() {
+        return None;
+    }
+    Some(ForeignKey {table: table.to_string(), col: col.to_string(), on_delete})
+}
+
+fn col_type_to_string(col: &Col) -> String {
+    let mut content = data_type_to_string(col.data_type);
+    if let Some(max_len) = col.max_len {
+        content.push_str(&format!("({max_len})"));
+    }
+    if col.nullable {
+        content.push('?');
+    }
+    match col.constraint {
+        Some(Constraint::PrimaryKey) => content.push_str("+primary"),
+        Some(Constraint::Unique) => content.push_str("+unique"),
+        None => (),
+    }
+    if let Some(ForeignKey {table, col, on_delete}) = &col.foreign_key {
+        content.push_str(&format!("+references({table}.{col}"));
+        if *on_delete == OnDelete::Cascade {
+            content.push_str(",cascade");
+        }
+        content.push(')');
+    }
+    if col.auto_increment {
+        content.push_str("+auto");
+    }
+    if let Some(default) = &col.default {
+        let literal = value_to_literal(default);
+        if literal.contains(['+', ')']) || literal.starts_with('"') {
+            content.push_str(&format!("+default(\"{literal}\")"));
+        } else {
+            content.push_str(&format!("+default({literal})"));
+        }
+    }
+    content
+}
+
+fn data_type_to_string(data_type: DataType) -> String {
+    match data_type {
+        DataType::Int   => "Int".to_string(),
+        DataType::Str   => "Str".to_string(),
+        DataType::Type  => "Type".to_string(), 
+        DataType::Bool      => "Bool".to_string(),
+        DataType::Float     => "Float".to_string(),
+        DataType::BigInt    => "BigInt".to_string(),
+        DataType::Timestamp => "Timestamp".to_string(),
+        DataType::Bytes     => "Bytes".to_string(),
+        DataType::Null | DataType::Count => unreachable!(),
+    }
+}
+
+fn parse_table_schema(file_path: &str) -> Result<TableSchema, Error> {
+    let mut file = File::open(file_path).map_err(io_error("open", file_path))?;
+
+    let mut content = String::new();
+    file.read_to_string(&mut content).map_err(io_error("read", file_path))?;
+    parse_schema(&content, file_path)
+}
+
+/// Parses the content of a schema file, `file_path` is only used in errors
+fn parse_schema(content: &str, file_path: &str) -> Result<TableSchema, Error> {
+    let schema_error = |line, kind| Error::SchemaParse {path: file_path.to_string(), line, kind};
+    let mut cols = vec![];
+    let mut lines = content.lines();
+    let name = match lines.next() {
+        Some(value) => value.trim(),
+        None => return Err(schema_error(1, SchemaParseError::MissingTableName)),
+    };
+
+    if name.is_empty() {
+        return Err(schema_error(1, SchemaParseError::EmptyTableName));
+    }
+
+    let mut indexes: Vec<Index> = vec![];
+    let mut sequence = None;
+    for (i, line) in lines.enumerate() {
+        let line_number = i + 2;
+        // Next value of the auto-increment column, `sequence <value>`
+        if let (Some(value), false) = (line.strip_prefix("sequence "), line.contains(':')) {
+            match value.trim().parse::<i64>() {
+                Ok(value) if sequence.is_none() => sequence = Some(value),
+                _ => return Err(schema_error(line_number, SchemaParseError::InvalidSequence)),
+            }
+            continue;
+        }
+        // Index of a column declared above, `index <name> <column>`
+        if let (Some(index), false) = (line.strip_prefix("index "), line.contains(':')) {
+            let (index_name, col_name) = match index.split_whitespace().collect::<Vec<&str>>()[..] {
+                [index_name, col_name] => (index_name, col_name),
+                _ => return Err(schema_error(line_number, SchemaParseError::InvalidIndexFormat)),
+            };
+            if indexes.iter().any(|index| index.name == index_name) {
+                return Err(schema_error(line_number, SchemaParseError::DuplicateIndex(index_name.to_string())));
+            }
+            match cols.iter().position(|Col {name, ..}| name == col_name) {
+                Some(col) if !matches!(cols[col].data_type, DataType::Int | DataType::Str) => {
+                    return Err(schema_error(line_number, SchemaParseError::NotIndexableColumn(col_name.to_string())));
+                },
+                Some(col) => indexes.push(Index::new(index_name.to_string(), col)),
+                None => return Err(schema_error(line_number, SchemaParseError::UnknownIndexColumn(col_name.to_string()))),
+            }
+            continue;
+        }
+        if !indexes.is_empty() || sequence.is_some() {
+            return Err(schema_error(line_number, SchemaParseError::ColumnAfterIndex));
+        }
+        let (name, type_name) = match line.split_once(':') {
+            Some((name, type_name)) => (name.trim(), type_name.trim()),
+            None => return Err(schema_error(line_number, SchemaParseError::InvalidColumnFormat)),
+        };
+
+        if name.is_empty() {
+            return Err(schema_error(line_number, SchemaParseError::EmptyColumnName));
+        }
+
+        for Col {name: col_name, ..} in &cols {
+            if col_name == name {
+                return Err(schema_error(line_number, SchemaParseError::DuplicateColumn(col_name.clone())));
+            } 
+        }
+
+        if let Some(col_type) = try_parse_col_type(type_name) {
+            if col_type.constraint == Some(Constraint::PrimaryKey) && has_primary_key(&cols) {
+                return Err(schema_error(line_number, SchemaParseError::MultiplePrimaryKeys));
+            }
+            if col_type.auto_increment && cols.iter().any(|col| col.auto_increment) {
+                return Err(schema_error(line_number, SchemaParseError::MultipleAutoIncrements));
+            }
+            cols.push(Col::new(String::from(name), col_type));
+        } else {
+            return Err(schema_error(line_number, SchemaParseError::UnknownType(type_name.to_string())));
+        } 
+    }
+
+    Ok(TableSchema { name: name.to_string(), cols, indexes, sequence: sequence.unwrap_or(1) })
+}
+
+fn has_primary_key(cols: &[Col]) -> bool {
+    cols.iter().any(|col| col.constraint == Some(Constraint::PrimaryKey))
+}
+
+fn try_parse_op(op: &str) -> Option<Op> {
+    assert!(Op::Count.as_u8() == 36, "Exhaustive Op handling in try_parse_op()");
+    match op {
+        "select" => Some(Op::Select),
+        "insert" => Some(Op::Insert),
+        "insert-named" => Some(Op::InsertNamed),
+        "delete" => Some(Op::Delete),
+        "update" => Some(Op::Update),
+        "create" => Some(Op::Create),
+        "drop"   => Some(Op::Drop),
+        "rename" => Some(Op::Rename),
+        "tables"   => Some(Op::Tables),
+        "describe" => Some(Op::Describe),
+        "info"     => Some(Op::Info),
+        "create-index" => Some(Op::CreateIndex),
+        "add-column"    => Some(Op::AddColumn),
+        "drop-column"   => Some(Op::DropColumn),
+        "rename-column" => Some(Op::RenameColumn),
+        "drop-index"   => Some(Op::DropIndex),
+        "order-by" => Some(Op::OrderBy),
+        "limit"    => Some(Op::Limit),
+        "offset"   => Some(Op::Offset),
+        "group-by" => Some(Op::GroupBy),
+        "having"   => Some(Op::Having),
+        "join"      => Some(Op::Join),
+        "left-join" => Some(Op::LeftJoin),
+        "count"  => Some(Op::Aggregate(Aggregate::Count)),
+        "sum"    => Some(Op::Aggregate(Aggregate::Sum)),
+        "min"    => Some(Op::Aggregate(Aggregate::Min)),
+        "max"    => Some(Op::Aggregate(Aggregate::Max)),
+        "avg"    => Some(Op::Aggregate(Aggregate::Avg)),
+        "begin"    => Some(Op::Begin),
+        "commit"   => Some(Op::Commit),
+        "rollback" => Some(Op::Rollback),
+        "and"    => Some(Op::And),
+        "or"     => Some(Op::Or),
+        "=="     => Some(Op::Equal),
+        "!="     => Some(Op::NotEqual),
+        ">"      => Some(Op::More),
+        "<"      => Some(Op::Less),
+        "is-null"     => Some(Op::IsNull),
+        "is-not-null" => Some(Op::IsNotNull),
+        _        => None,  
+    }
+}
+
+fn parse_query(query: &str) -> Result<Vec<Token>, Error> {
+    let mut tokens: Vec<Token> = vec![];
+    let source = query;
+    let mut query = query;
+    loop {
+        query = query.trim_start();
+        if query.is_empty() { break; }
+        let end = match query.find(char::is_whitespace) {
+            Some(end) => end,
+            None => query.len(),
+        };
+        let word = &query[0..end];
+        if let Some(op) = try_parse_op(word) {
+            tokens.push(Token {op, pos: source.len() - query.len()}); 
+            query = &query[end..];
+            continue;
+        }
+        // Parentheses are a part of types like `Str(20)`
+        if let Some(col_type) = try_parse_col_type(word) {
+            let op = Op::PushWord {data_type: DataType::Type, word_type: WordType::Type(col_type)};
+            tokens.push(Token {op, pos: source.len() - query.len()}); 
+            query = &query[end..];
+            continue;
+        }
+
+        query = query.trim_start_matches(['(', ')']);
+        let pos = source.len() - query.len();
+        if query.bytes().next().unwrap() == b'"' {
+            query = &query[1..]; 
+            if let Some(end) = query.find('"') {
+                tokens.push(Token {
+                    op: Op::PushWord {
+                        data_type: DataType::Str, 
+                        word_type: WordType::Str(String::from(&query[0..end]))
+                    },
+                    pos,
+                });
+                query = &query[end+1..];
+            } else {
+                return Err(Error::UnclosedString {pos});
+            }
+        } else {
+            let end = match query.find(char::is_whitespace) {
+                Some(end) => end,
+                None => query.len(),
+            };
+            let mut word = query[0..end].to_string();
+            word = word.replace('(', "");
+            word = word.replace(')', "");
+            query = &query[end..];
+            let op = if let Some(op) = try_parse_op(&word) {
+                op
+            } else if let Some(col_type) = try_parse_col_type(&word) {
+                Op::PushWord {
+                    data_type: DataType::Type, 
+                    word_type: WordType::Type(col_type) 
+                }
+            } else if word == "null" {
+                Op::PushWord {
+                    data_type: DataType::Null, 
+                    word_type: WordType::Null 
+                }
+            } else if word == "true" || word == "false" {
+                Op::PushWord {
+                    data_type: DataType::Bool, 
+                    word_type: WordType::Bool(word == "true") 
+                }
+            } else if let Ok(value) = word.parse::<i32>() {
+                Op::PushWord {
+                    data_type: DataType::Int, 
+                    word_type: WordType::Int(value) 
+                }
+            } else if let Some(Ok(value)) = word.strip_suffix('L').map(str::parse::<i64>) {
+                Op::PushWord {
+                    data_type: DataType::BigInt, 
+                    word_type: WordType::BigInt(value) 
+                }
+            } else if let Some(value) = try_parse_float(&word) {
+                Op::PushWord {
+                    data_type: DataType::Float, 
+                    word_type: WordType::Float(Float(value)) 
+                }
+            } else if let Some(value) = timestamp::parse(&word) {
+                Op::PushWord {
+                    data_type: DataType::Timestamp, 
+                    word_type: WordType::Timestamp(value) 
+                }
+            } else if let Some(value) = try_parse_bytes(&word) {
+                Op::PushWord {
+                    data_type: DataType::Bytes, 
+                    word_type: WordType::Bytes(value) 
+                }
+            } else {
+                Op::PushWord {
+                    data_type: DataType::Str, 
+                    word_type: WordType::Str(word)
+                }
+            };
+            tokens.push(Token {op, pos});
+        }
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug, PartialEq)]
+struct Condition {
+    idx: usize,
+    value: WordType,
+    op: Op,
+}
+
+// TODO: Maybe change table with schema
+fn logical_op_check(op: Op, col: Word, value: Word, table: &Table) -> Result<Condition, Error> {
+    let col_name = match col.value {
+        WordType::Str(value) => value,
+        other => return Err(Error::UnexpectedWord {expected: "column name", found: other, pos: col.pos}),
+    };
+
+    let mut idx = table.schema.cols.len();
+    for (i, Col {name, ..}) in table.schema.cols.iter().enumerate() {
+        if *name == col_name {
+            idx = i;
+            break;
+        }
+    }
+
+    if idx == table.schema.cols.len() {
+        return Err(Error::UnknownColumn {table: table.schema.name.clone(), column: col_name, pos: col.pos});
+    }
+
+    // Nothing is equal to NULL, so only `is-null` and `is-not-null` can look for it
+    let col_data_type = table.schema.cols[idx].data_type;
+    if !matches!(op, Op::IsNull | Op::IsNotNull) && value.data_type != col_data_type {
+        return Err(Error::TypeMismatch {column: col_name, expected: col_data_type, found: value.data_type, pos: value.pos});
+    }
+    
+    Ok(Condition {
+        idx,
+        value: value.value,
+        op,
+    })
+}
+
+/// Comparison of NULL with any value is false, only `is-null` and `is-not-null` match it
+fn filter_condition(a: &WordType, b: &WordType, condition: Op) -> bool {
+    assert!(Op::Count.as_u8() == 36, "Exhaustive logic Ops handling in filter_condition()");
+    match condition {
+        Op::IsNull    => *a == WordType::Null,
+        Op::IsNotNull => *a != WordType::Null,
+        _ if *a == WordType::Null || *b == WordType::Null => false,
+        Op::Equal     => *a == *b,
+        Op::NotEqual  => *a != *b,
+        Op::Less      => compare_words(a, b) != Ordering::Greater,
+        Op::More      => compare_words(a, b) != Ordering::Less,
+        _             => unreachable!(),
+    }
+}
+
+/// Orders words of the same type, NULL goes before any value
+fn compare_words(a: &WordType, b: &WordType) -> Ordering {
+    match (a, b) {
+        (WordType::Null, WordType::Null) => Ordering::Equal,
+        (WordType::Null, _) => Ordering::Less,
+        (_, WordType::Null) => Ordering::Greater,
+        (WordType::Int(a), WordType::Int(b)) => a.cmp(b),
+        (WordType::Str(a), WordType::Str(b)) => a.cmp(b),
+        (WordType::Bool(a), WordType::Bool(b)) => a.cmp(b),
+        (WordType::Float(a), WordType::Float(b)) => a.cmp(b),
+        (WordType::BigInt(a), WordType::BigInt(b)) => a.cmp(b),
+        (WordType::Timestamp(a), WordType::Timestamp(b)) => a.cmp(b),
+        (WordType::Bytes(a), WordType::Bytes(b)) => a.cmp(b),
+        _ => unreachable!("words of different types in one column"),
+    }
+}
+
+/// Checks that the word can be stored in the column
+fn check_value(col: &Col, word: &Word) -> Result<(), Error> {
+    if word.data_type == DataType::Null {
+        if !col.nullable {
+            return Err(Error::NullNotAllowed {column: col.name.clone(), pos: word.pos});
+        }
+    } else if word.data_type != col.data_type {
+        return Err(Error::TypeMismatch {
+            column: col.name.clone(),
+            expected: col.data_type,
+            found: word.data_type,
+            pos: word.pos,
+        });
+    }
+    if let (Some(max_len), WordType::Str(value)) = (col.max_len, &word.value) {
+        if value.chars().count() > max_len as usize {
+            return Err(Error::StringTooLong {column: col.name.clone(), max_len, pos: word.pos});
+        }
+    }
+    Ok(())
+}
+
+/// Checks that no other row has the value set to a unique column of the `changed` rows
+fn check_unique(database: &Database, table_idx: usize, col: usize, word: &Word, changed: &[usize]) -> Result<(), Error> {
+    let table = &database.tables[table_idx];
+    if table.schema.cols[col].constraint.is_none() || word.value == WordType::Null {
+        return Ok(());
+    }
+    let conflict = changed.len() > 1 || match table.schema.indexes.iter().find(|index| index.col == col) {
+        Some(index) => index.lookup(&Op::Equal, &word.value).iter().any(|row| changed.binary_search(row).is_err()),
+        None => {
+            let mut found = false;
+            scan_rows(database, table_idx, None, |i, row| {
+                found = row[col] == word.value && changed.binary_search(&i).is_err();
+                !found
+            })?;
+            found
+        },
+    };
+    if conflict {
+        return Err(Error::DuplicateValue {
+            table: table.schema.name.clone(),
+            column: table.schema.cols[col].name.clone(),
+            value: word.value.clone(),
+            pos: word.pos,
+        });
+    }
+    Ok(())
+}
+
+/// Renames the column and foreign keys referencing it, returns the old name
+fn rename_column(database: &mut Database, table_idx: usize, col: usize, name: String) -> String {
+    let children = referencing_cols(database, table_idx, col);
+    let old_name = std::mem::replace(&mut database.tables[table_idx].schema.cols[col].name, name.clone());
+    for (child_idx, child_col) in children {
+        if let Some(foreign_key) = &mut database.tables[child_idx].schema.cols[child_col].foreign_key {
+            foreign_key.col = name.clone();
+        }
+    }
+    old_name
+}
+
+/// Renames the table and foreign keys which reference it
+fn rename_table(database: &mut Database, table_idx: usize, name: String) {
+    let old_name = database.tables[table_idx].schema.name.clone();
+    for table in &mut database.tables {
+        for col in &mut table.schema.cols {
+            match &mut col.foreign_key {
+                Some(foreign_key) if foreign_key.table == old_name => foreign_key.table = name.clone(),
+                _ => {},
+            }
+        }
+    }
+    if let Some(stored) = database.paged.tables.remove(&old_name) {
+        database.paged.tables.insert(name.clone(), stored);
+    }
+    database.tables[table_idx].schema.name = name;
+}
+
+/// Name of a column of the table, which must not exist for `new` or must exist otherwise
+fn col_name_check(word: Option<Word>, table: &Table, new: bool, op: &Token) -> Result<(String, usize), Error> {
+    let word = match word {
+        Some(word) => word,
+        None => return Err(Error::StackUnderflow {op: op.op.name(), needed: 2, found: 1, pos: op.pos}),
+    };
+    let name = match word.value {
+        WordType::Str(name) => name,
+        other => return Err(Error::UnexpectedWord {expected: "column name", found: other, pos: word.pos}),
+    };
+    match table.schema.cols.iter().position(|col| col.name == name) {
+        Some(_) if new => Err(Error::ColumnExists {table: table.schema.name.clone(), column: name, pos: word.pos}),
+        Some(idx) => Ok((name, idx)),
+        None if new => Ok((name, table.schema.cols.len())),
+        None => Err(Error::UnknownColumn {table: table.schema.name.clone(), column: name, pos: word.pos}),
+    }
+}
+
+/// Checks the values of a new row against the columns and their constraints and inserts it
+fn insert_row(database: &mut Database, table_idx: usize, row: Vec<Word>) -> Result<(), Error> {
+    let table = &database.tables[table_idx];
+    for (i, word) in row.iter().enumerate() {
+        check_value(&table.schema.cols[i], word)?;
+        check_unique(database, table_idx, i, word, &[])?;
+        check_parent(database, &table.schema.cols[i], word)?;
+    }
+    let record = LogRecord::Insert {
+        table: table.schema.name.clone(),
+        row: row.into_iter().map(|word| word.value).collect(),
+    };
+    log_and_apply(database, record)
+}
+
+/// Next value of the sequence of the table for its auto-increment column
+fn next_sequence_value(table: &Table, pos: usize) -> Result<Word, Error> {
+    match i32::try_from(table.schema.sequence) {
+        Ok(value) => Ok(Word {data_type: DataType::Int, value: WordType::Int(value), pos}),
+        Err(_) => Err(Error::IntegerOverflow {op: "insert", pos}),
+    }
+}
+
+/// Checks that a foreign key declared in `create` references a unique column of the same type
+fn check_foreign_key(database: &Database, schema: &TableSchema, col: &Col, pos: usize) -> Result<(), Error> {
+    let foreign_key = match &col.foreign_key {
+        Some(foreign_key) => foreign_key,
+        None => return Ok(()),
+    };
+    let invalid = |reason| Err(Error::InvalidForeignKey {column: col.name.clone(), reason, pos});
+    let parent = match table_idx_by_name(database, &foreign_key.table) {
+        Some(idx) => &database.tables[idx].schema,
+        None if foreign_key.table == schema.name => schema,
+        None => return invalid("parent table doesn't exist"),
+    };
+    let parent_col = match parent.cols.iter().find(|parent_col| parent_col.name == foreign_key.col) {
+        Some(parent_col) => parent_col,
+        None => return invalid("parent column doesn't exist"),
+    };
+    if parent_col.constraint.is_none() {
+        return invalid("parent column isn't a primary key or unique");
+    }
+    if parent_col.data_type != col.data_type {
+        return invalid("parent column has another type");
+    }
+    Ok(())
+}
+
+/// Parent table and column of the foreign key
+fn foreign_key_parent(database: &Database, foreign_key: &ForeignKey) -> Option<(usize, usize)> {
+    let table_idx = table_idx_by_name(database, &foreign_key.table)?;
+    let col = database.tables[table_idx].schema.cols.iter().position(|col| col.name == foreign_key.col)?;
+    Some((table_idx, col))
+}
+
+/// Checks that the value of the word set to a foreign key column exists in the parent column
+fn check_parent(database: &Database, col: &Col, word: &Word) -> Result<(), Error> {
+    let foreign_key = match &col.foreign_key {
+        Some(foreign_key) if word.value != WordType::Null => foreign_key,
+        _ => return Ok(()),
+    };
+    let found = match foreign_key_parent(database, foreign_key) {
+        Some((table_idx, parent_col)) => contains_value(database, table_idx, parent_col, &word.value)?,
+        None => false,
+    };
+    if !found {
+        return Err(Error::MissingParent {
+            column: col.name.clone(),
+            parent: foreign_key.table.clone(),
+            value: word.value.clone(),
+            pos: word.pos,
+        });
+    }
+    Ok(())
+}
+
+/// Whether any row of the table has the value in the column
+fn contains_value(database: &Database, table_idx: usize, col: usize, value: &WordType) -> Result<bool, Error> {
+    let table = &database.tables[table_idx];
+    if let Some(index) = table.schema.indexes.iter().find(|index| index.col == col) {
+        return Ok(index.tree.contains_key(value));
+    }
+    let mut found = false;
+    scan_rows(database, table_idx, None, |_, row| {
+        found = row[col] == *value;
+        !found
+    })?;
+    Ok(found)
+}
+
+/// Foreign key columns referencing a column of the table, as pairs of the child table and column
+fn referencing_cols(database: &Database, table_idx: usize, col: usize) -> Vec<(usize, usize)> {
+    let parent = &database.tables[table_idx].schema;
+    let mut children = vec![];
+    for (child_idx, child) in database.tables.iter().enumerate() {
+        for (child_col, Col {foreign_key, ..}) in child.schema.cols.iter().enumerate() {
+            if let Some(foreign_key) = foreign_key {
+                if foreign_key.table == parent.name && foreign_key.col == parent.cols[col].name {
+                    children.push((child_idx, child_col));
+                }
+            }
+        }
+    }
+    children
+}
+
+/// Positions of rows of a child table which reference any of the values
+fn referencing_rows(database: &Database, child_idx: usize, child_col: usize, values: &[WordType]) -> Result<Vec<usize>, Error> {
+    let mut rows = vec![];
+    scan_rows(database, child_idx, None, |i, row| {
+        if row[child_col] != WordType::Null && values.contains(&row[child_col]) {
+            rows.push(i);
+        }
+        true
+    })?;
+    Ok(rows)
+}
+
+/// Checks that a column referenced by foreign keys isn't changed in rows which have children
+fn check_not_referenced(database: &Database, table_idx: usize, col: usize, rows: &[usize], word: &Word) -> Result<(), Error> {
+    let children = referencing_cols(database, table_idx, col);
+    if children.is_empty() {
+        return Ok(());
+    }
+    let values: Vec<WordType> = rows_at(database, table_idx, rows)?.into_iter()
+        .map(|mut row| row.swap_remove(col))
+        .filter(|value| *value != word.value)
+        .collect();
+    for (child_idx, child_col) in children {
+        if let Some(row) = referencing_rows(database, child_idx, child_col, &values)?.first() {
+            return Err(Error::RowReferenced {
+                table: database.tables[table_idx].schema.name.clone(),
+                child: database.tables[child_idx].schema.name.clone(),
+                value: rows_at(database, child_idx, &[*row])?.swap_remove(0).swap_remove(child_col),
+                pos: word.pos,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Deletes the rows and, following foreign keys, the child rows which reference them
+fn delete_rows(database: &mut Database, table_idx: usize, rows: Vec<usize>, pos: usize) -> Result<(), Error> {
+    if rows.is_empty() {
+        return Ok(());
+    }
+    let table = &database.tables[table_idx];
+    let mut deleted_values = vec![];
+    let mut deleted_rows = None;
+    for col in 0..table.schema.cols.len() {
+        let children = referencing_cols(database, table_idx, col);
+        if !children.is_empty() {
+            if deleted_rows.is_none() {
+                deleted_rows = Some(rows_at(database, table_idx, &rows)?);
+            }
+            let values: Vec<WordType> = deleted_rows.iter().flatten().map(|row| row[col].clone()).collect();
+            deleted_values.push((values, children));
+        }
+    }
+    let table_name = table.schema.name.clone();
+    log_and_apply(database, LogRecord::Delete {table: table_name.clone(), rows})?;
+
+    for (values, children) in deleted_values {
+        for (child_idx, child_col) in children {
+            // Children are found after the delete, so rows of a table referencing itself aren't
+            let child_rows = referencing_rows(database, child_idx, child_col, &values)?;
+            let child = &database.tables[child_idx].schema;
+            match child_rows.first() {
+                None => (),
+                Some(row) if child.cols[child_col].foreign_key.as_ref().unwrap().on_delete == OnDelete::Restrict => {
+                    return Err(Error::RowReferenced {
+                        table: table_name,
+                        child: child.name.clone(),
+                        value: rows_at(database, child_idx, &[*row])?.swap_remove(0).swap_remove(child_col),
+                        pos,
+                    });
+                },
+                Some(_) => delete_rows(database, child_idx, child_rows, pos)?,
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Restricts the drop of a table or deletes rows which reference it, following foreign keys
+fn drop_references(database: &mut Database, table_idx: usize, pos: usize) -> Result<(), Error> {
+    let table_name = database.tables[table_idx].schema.name.clone();
+    for col in 0..database.tables[table_idx].schema.cols.len() {
+        for (child_idx, child_col) in referencing_cols(database, table_idx, col) {
+            let child = &database.tables[child_idx].schema;
+            if child.name == table_name {
+                continue;
+            }
+            if child.cols[child_col].foreign_key.as_ref().unwrap().on_delete == OnDelete::Restrict {
+                return Err(Error::TableReferenced {table: table_name, child: child.name.clone(), pos});
+            }
+            let child_name = child.name.clone();
+            let mut child_rows = vec![];
+            scan_rows(database, child_idx, None, |i, row| {
+                if row[child_col] != WordType::Null {
+                    child_rows.push(i);
+                }
+                true
+            })?;
+            delete_rows(database, child_idx, child_rows, pos)?;
+            log_and_apply(database, LogRecord::DropForeignKey {table: child_name, col: child_col})?;
+        }
+    }
+    Ok(())
+}
+
+fn table_name_check(name: Word, database: &Database) -> Result<usize, Error> {
+    let table_name = match name.value {
+        WordType::Str(name) => name,
+        other => return Err(Error::UnexpectedWord {expected: "table name", found: other, pos: name.pos}),
+    };
+
+    match table_idx_by_name(database, &table_name) {
+        Some(idx) => Ok(idx),
+        None if database.is_quarantined(&table_name) => Err(Error::TableQuarantined {table: table_name, pos: name.pos}),
+        None => Err(Error::UnknownTable {table: table_name, pos: name.pos}),
+    }
+}
+
+/// Name of a table which is going to be created
+fn new_table_name_check(name: Word, database: &Database) -> Result<String, Error> {
+    match name.value {
+        WordType::Str(table) if table_idx_by_name(database, &table).is_some() => Err(Error::TableExists {table, pos: name.pos}),
+        WordType::Str(table) if database.is_quarantined(&table) => Err(Error::TableQuarantined {table, pos: name.pos}),
+        WordType::Str(table) => Ok(table),
+        other => Err(Error::UnexpectedWord {expected: "table name", found: other, pos: name.pos}),
+    }
+}
+
+fn table_idx_for(op: &Token, word: Option<Word>, database: &Database) -> Result<usize, Error> {
+    match word {
+        Some(word) => table_name_check(word, database),
+        None => Err(Error::StackUnderflow {op: op.op.name(), needed: 1, found: 0, pos: op.pos}),
+    }
+}
+
+/// Comparison with its column and value words or a logical operation on results of previous conditions
+struct RawCondition {
+    op: Op,
+    operands: Option<(Word, Word)>,
+    pos: usize,
+}
+
+/// Checks conditions against the table and makes sure they produce exactly one value
+fn compile_conditions(conditions: &[RawCondition], table: &Table, pos: usize) -> Result<Vec<Condition>, Error> {
+    let mut comp_conds = vec![]; 
+    let mut stack_len = 0;
+    for condition in conditions {
+        match &condition.operands {
+            None => {
+                if stack_len < 2 {
+                    return Err(Error::StackUnderflow {op: condition.op.name(), needed: 2, found: stack_len, pos: condition.pos});
+                }
+                stack_len -= 1;
+                comp_conds.push(Condition {
+                    idx: 0,
+                    value: WordType::Int(0),
+                    op: condition.op.clone(), 
+                });
+            },
+            Some((col, value)) => {
+                stack_len += 1;
+                comp_conds.push(logical_op_check(condition.op.clone(), col.clone(), value.clone(), table)?);
+            },
+        }
+    }
+    if !conditions.is_empty() && stack_len != 1 {
+        return Err(Error::InvalidConditions {count: stack_len, pos});
+    }
+    Ok(comp_conds)
+}
+
+fn check_conditions(conditions: &[Condition], row: &Row) -> bool {
+    let mut cond_stack = vec![];
+    for condition in conditions {
+        match &condition.op {
+            Op::And => {
+                let a = cond_stack.pop().unwrap();
+                let b = cond_stack.pop().unwrap();
+                cond_stack.push(a & b);
+            },
+            Op::Or => {
+                let a = cond_stack.pop().unwrap();
+                let b = cond_stack.pop().unwrap();
+                cond_stack.push(a | b);
+            },
+            _ => {
+                cond_stack.push(filter_condition(&row[condition.idx], &condition.value, condition.op.clone())); 
+            },
+        }
+    }
+    cond_stack.pop().unwrap()
+}
+
+/// Positions of rows which may match the conditions, `None` when all rows must be scanned
+fn index_candidates(table: &Table, conditions: &[Condition]) -> Option<Vec<usize>> {
+    let mut stack: Vec<Option<Vec<usize>>> = vec![];
+    for condition in conditions {
+        match &condition.op {
+            Op::And => {
+                let a = stack.pop().unwrap();
+                let b = stack.pop().unwrap();
+                stack.push(match (a, b) {
+                    (Some(mut a), Some(b)) => {
+                        a.retain(|row| b.binary_search(row).is_ok());
+                        Some(a)
+                    },
+                    (a, b) => a.or(b),
+                });
+            },
+            Op::Or => {
+                let a = stack.pop().unwrap();
+                let b = stack.pop().unwrap();
+                stack.push(match (a, b) {
+                    (Some(mut a), Some(b)) => {
+                        a.extend(b);
+                        a.sort_unstable();
+                        a.dedup();
+                        Some(a)
+                    },
+                    _ => None,
+                });
+            },
+            op @ (Op::Equal | Op::Less | Op::More) => {
+                let index = table.schema.indexes.iter().find(|index| index.col == condition.idx);
+                stack.push(index.map(|index| index.lookup(op, &condition.value)));
+            },
+            _ => stack.push(None),
+        }
+    }
+    stack.pop().flatten()
+}
+
+/// Positions of rows of the table matching the conditions, every row matches no conditions
+fn matching_rows(database: &Database, table_idx: usize, conditions: &[Condition]) -> Result<Vec<usize>, Error> {
+    let candidates = index_candidates(&database.tables[table_idx], conditions);
+    let mut rows = vec![];
+    scan_rows(database, table_idx, candidates.as_deref(), |i, row| {
+        if conditions.is_empty() || check_conditions(conditions, row) {
+            rows.push(i);
+        }
+        true
+    })?;
+    Ok(rows)
+}
+
+fn table_idx_by_name(database: &Database, name: &str) -> Option<usize> {
+    database.tables.iter().position(|table| table.schema.name == name)
+}
+
+/// Applies the change to the tables and returns the change which reverts it.
+fn apply_record(database: &mut Database, record: LogRecord) -> Result<Undo, Error> {
+    let table_idx = |database: &Database, name: &str| match table_idx_by_name(database, name) {
+        Some(idx) => Ok(idx),
+        None => Err(Error::CorruptedLog {path: database.format.log_path(&database.path), detail: format!("change of unknown table `{name}`")}),
+    };
+    let undo = match record {
+        LogRecord::Insert {table, row} => {
+            let idx = table_idx(database, &table)?;
+            let (schema, mut rows) = table_rows_mut(database, idx);
+            for index in &mut schema.indexes {
+                index.insert(rows.len(), &row[index.col]);
+            }
+            // Replayed rows move the sequence past their values
+            let auto_increment = schema.cols.iter().position(|col| col.auto_increment);
+            if let Some(WordType::Int(value)) = auto_increment.map(|col| &row[col]) {
+                schema.sequence = schema.sequence.max(*value as i64 + 1);
+            }
+            rows.push(row);
+            Undo::RemoveLastRow {table}
+        },
+        LogRecord::Delete {table, rows} => {
+            let idx = table_idx(database, &table)?;
+            load_positions(database, idx, &rows)?;
+            let (schema, mut table_rows) = table_rows_mut(database, idx);
+            let mut deleted = vec![];
+            for (i, row) in rows.iter().enumerate() {
+                deleted.push((*row, table_rows.remove(row - i)));
+            }
+            for index in &mut schema.indexes {
+                index.remove_rows(&rows);
+            }
+            Undo::InsertRows {table, rows: deleted}
+        },
+        LogRecord::Update {table, rows, values} => {
+            let idx = table_idx(database, &table)?;
+            load_positions(database, idx, &rows)?;
+            let (schema, mut table_rows) = table_rows_mut(database, idx);
+            let mut updated = vec![];
+            for row in rows {
+                let current = table_rows.get_mut(row);
+                updated.push((row, current.clone()));
+                for (col, value) in &values {
+                    for index in schema.indexes.iter_mut().filter(|index| index.col == *col) {
+                        index.remove(row, &current[*col]);
+                        index.insert(row, value);
+                    }
+                    current[*col] = value.clone();
+                }
+            }
+            // Updated values of the auto-increment column move the sequence past them
+            for (col, value) in &values {
+                if let (true, WordType::Int(value)) = (schema.cols[*col].auto_increment, value) {
+                    schema.sequence = schema.sequence.max(*value as i64 + 1);
+                }
+            }
+            Undo::SetRows {table, rows: updated}
+        },
+        LogRecord::Create {schema} => {
+            let table = schema.name.clone();
+            database.tables.push(Table {
+                schema,
+                rows: vec![],
+            });
+            Undo::RemoveTable {table}
+        },
+        LogRecord::Drop {table} => {
+            let idx = table_idx(database, &table)?;
+            // Rollback restores the table with its stored rows
+            let stored = database.paged.tables.remove(&table);
+            Undo::InsertTable {idx, table: database.tables.remove(idx), stored}
+        },
+        LogRecord::DropForeignKey {table, col} => {
+            let idx = table_idx(database, &table)?;
+            let foreign_key = database.tables[idx].schema.cols[col].foreign_key.take();
+            Undo::SetForeignKey {table, col, foreign_key}
+        },
+        LogRecord::AddColumn {table, col} => {
+            let idx = table_idx(database, &table)?;
+            load_rows(database, idx)?;
+            let log_path = database.format.log_path(&database.path);
+            let table_ref = &mut database.tables[idx];
+            let schema = table_ref.schema.clone();
+            // Rows get the default value, values of the sequence or NULL
+            let sequence = table_ref.schema.sequence;
+            let values = (0..table_ref.rows.len() as i64).map(|i| match &col.default {
+                _ if col.auto_increment => i32::try_from(sequence + i).map(WordType::Int).map_err(|_| Error::CorruptedLog {
+                    path: log_path.clone(),
+                    detail: format!("sequence of table `{table}` doesn't fit into Int"),
+                }),
+                Some(default) => Ok(default.clone()),
+                None => Ok(WordType::Null),
+            }).collect::<Result<Vec<_>, _>>()?;
+            if col.auto_increment {
+                table_ref.schema.sequence += values.len() as i64;
+            }
+            for (row, value) in table_ref.rows.iter_mut().zip(values) {
+                row.push(value);
+            }
+            table_ref.schema.cols.push(col);
+            Undo::RemoveLastColumn {table, schema}
+        },
+        LogRecord::DropColumn {table, col} => {
+            let idx = table_idx(database, &table)?;
+            load_rows(database, idx)?;
+            let table_ref = &mut database.tables[idx];
+            let schema = table_ref.schema.clone();
+            let values = table_ref.rows.iter_mut().map(|row| row.remove(col)).collect();
+            table_ref.schema.cols.remove(col);
+            table_ref.schema.indexes.retain(|index| index.col != col);
+            for index in &mut table_ref.schema.indexes {
+                if index.col > col {
+                    index.col -= 1;
+                }
+            }
+            Undo::InsertColumn {table, schema, col, values}
+        },
+        LogRecord::RenameColumn {table, col, name} => {
+            let idx = table_idx(database, &table)?;
+            let name = rename_column(database, idx, col, name);
+            Undo::RenameColumn {table, col, name}
+        },
+        LogRecord::RenameTable {table, name} => {
+            let idx = table_idx(database, &table)?;
+            rename_table(database, idx, name.clone());
+            Undo::RenameTable {table: name, name: table}
+        },
+        LogRecord::CreateIndex {table, name, col} => {
+            let idx = table_idx(database, &table)?;
+            let mut index = Index::new(name.clone(), col);
+            match database.paged.tables.get(&table) {
+                Some(stored) => scan_into_indexes(stored, &database.tables[idx].schema, std::slice::from_mut(&mut index))?,
+                None => index.build(&database.tables[idx].rows),
+            }
+            database.tables[idx].schema.indexes.push(index);
+            Undo::RemoveIndex {table, name}
+        },
+        LogRecord::DropIndex {table, name} => {
+            let idx = table_idx(database, &table)?;
+            let indexes = &mut database.tables[idx].schema.indexes;
+            let index_idx = match indexes.iter().position(|index| index.name == name) {
+                Some(index_idx) => index_idx,
+                None => return Err(Error::CorruptedLog {path: database.format.log_path(&database.path), detail: format!("drop of unknown index `{name}`")}),
+            };
+            Undo::InsertIndex {table, idx: index_idx, index: indexes.remove(index_idx)}
+        },
+    };
+    Ok(undo)
+}
+
+fn apply_undo(database: &mut Database, undo: Undo) {
+    let table_idx = |database: &Database, name: &str| table_idx_by_name(database, name)
+        .expect("undo records are applied to the same tables as the changes");
+    match undo {
+        // Changed rows are still loaded, so their indexes are changed back without reading pages
+        Undo::RemoveLastRow {table} => {
+            let idx = table_idx(database, &table);
+            let (schema, mut rows) = table_rows_mut(database, idx);
+            let row = rows.pop();
+            for index in &mut schema.indexes {
+                index.remove(rows.len(), &row[index.col]);
+            }
+        },
+        Undo::InsertRows {table, rows} => {
+            let idx = table_idx(database, &table);
+            let (schema, mut table_rows) = table_rows_mut(database, idx);
+            for index in &mut schema.indexes {
+                index.insert_rows(&rows);
+            }
+            for (i, row) in rows {
+                table_rows.insert(i, row);
+            }
+        },
+        Undo::SetRows {table, rows} => {
+            let idx = table_idx(database, &table);
+            let (schema, mut table_rows) = table_rows_mut(database, idx);
+            for (i, row) in rows {
+                let current = table_rows.get_mut(i);
+                for index in &mut schema.indexes {
+                    index.remove(i, &current[index.col]);
+                    index.insert(i, &row[index.col]);
+                }
+                *current = row;
+            }
+        },
+        Undo::RemoveTable {table} => {
+            let idx = table_idx(database, &table);
+            database.tables.remove(idx);
+        },
+        Undo::InsertTable {idx, table, stored} => {
+            if let Some(stored) = stored {
+                database.paged.tables.insert(table.schema.name.clone(), stored);
+            }
+            database.tables.insert(idx, table);
+        },
+        Undo::SetForeignKey {table, col, foreign_key} => {
+            let idx = table_idx(database, &table);
+            database.tables[idx].schema.cols[col].foreign_key = foreign_key;
+        },
+        Undo::RemoveLastColumn {table, schema} => {
+            let idx = table_idx(database, &table);
+            for row in &mut database.tables[idx].rows {
+                row.pop();
+            }
+            database.tables[idx].schema = schema;
+            rebuild_indexes(&mut database.tables[idx]);
+        },
+        Undo::InsertColumn {table, schema, col, values} => {
+            let idx = table_idx(database, &table);
+            for (row, value) in database.tables[idx].rows.iter_mut().zip(values) {
+                row.insert(col, value);
+            }
+            database.tables[idx].schema = schema;
+            rebuild_indexes(&mut database.tables[idx]);
+        },
+        Undo::RenameColumn {table, col, name} => {
+            let idx = table_idx(database, &table);
+            rename_column(database, idx, col, name);
+        },
+        Undo::RenameTable {table, name} => {
+            let idx = table_idx(database, &table);
+            rename_table(database, idx, name);
+        },
+        Undo::RemoveIndex {table, name} => {
+            let idx = table_idx(database, &table);
+            database.tables[idx].schema.indexes.retain(|index| index.name != name);
+        },
+        // Rows didn't change since the drop, so the tree of the index is still valid
+        Undo::InsertIndex {table, idx, index} => {
+            let table_idx = table_idx(database, &table);
+            database.tables[table_idx].schema.indexes.insert(idx, index);
+        },
+    }
+}
+
+/// Applies the change as a part of the current transaction.
+fn log_and_apply(database: &mut Database, record: LogRecord) -> Result<(), Error> {
+    let undo = apply_record(database, record.clone())?;
+    let transaction = database.transaction.get_or_insert_with(|| Transaction::new(false));
+    transaction.records.push(record);
+    transaction.undo.push(undo);
+    Ok(())
+}
+
+/// Makes changes of the current transaction durable in the write-ahead log
+fn commit_transaction(database: &mut Database) -> Result<(), Error> {
+    let transaction = match database.transaction.take() {
+        Some(transaction) => transaction,
+        None => return Ok(()),
+    };
+    if database.path.is_empty() || transaction.records.is_empty() {
+        return Ok(());
+    }
+    if let Err(err) = wal::append(&database.format.log_path(&database.path), &transaction.records) {
+        rollback_transaction(database, transaction);
+        return Err(err);
+    }
+    Ok(())
+}
+
+fn rollback_transaction(database: &mut Database, transaction: Transaction) {
+    for undo in transaction.undo.into_iter().rev() {
+        apply_undo(database, undo);
+    }
+}
+
+/// Takes names of the selected columns from the words stack, `*` selects every column
+fn select_cols(words: &mut Vec<Word>, table: &Table, op: &Token) -> Result<Vec<usize>, Error> {
+    let mut row_idxs = vec![];
+    'outer: while let Some(word) = words.pop() {
+        match word.value {
+            WordType::Str(ref value) => {
+                if value == "*" {
+                    row_idxs.append(&mut (0..table.schema.cols.len()).rev().collect::<Vec<usize>>());
+                    continue;
+                }
+
+                for (i, Col {name, ..}) in table.schema.cols.iter().enumerate() {
+                    if name == value {
+                        row_idxs.push(i);
+                        continue 'outer;
+                    }
+                }
+                return Err(Error::UnknownColumn {
+                    table: table.schema.name.clone(),
+                    column: value.clone(),
+                    pos: word.pos,
+                });
+            },
+            _ => {
+                words.push(word);
+                break;
+            },
+        }
+    }
+    if row_idxs.is_empty() {
+        return Err(Error::MissingColumns {op: op.op.name(), pos: op.pos});
+    }
+    row_idxs.reverse();
+    Ok(row_idxs)
+}
+
+/// Pairs rows of the tables with equal values in the key columns
+fn join_rows(left: &Table, right: &Table, left_key: usize, right_key: usize, left_join: bool) -> Vec<Row> {
+    let mut rows = vec![];
+    let mut push_matches = |row: &Row, matches: &mut dyn Iterator<Item = &Row>| {
+        let len = rows.len();
+        for right_row in matches {
+            rows.push(row.iter().chain(right_row).cloned().collect());
+        }
+        if left_join && rows.len() == len {
+            rows.push(row.iter().cloned().chain(right.schema.cols.iter().map(|_| WordType::Null)).collect());
+        }
+    };
+
+    match right.schema.cols[right_key].data_type {
+        DataType::Int | DataType::Str | DataType::BigInt | DataType::Timestamp | DataType::Bytes => {
+            let mut hashed: HashMap<&WordType, Vec<&Row>> = HashMap::new();
+            for row in &right.rows {
+                hashed.entry(&row[right_key]).or_default().push(row);
+            }
+            hashed.remove(&WordType::Null);
+            for row in &left.rows {
+                let matches = hashed.get(&row[left_key]).map(Vec::as_slice).unwrap_or_default();
+                push_matches(row, &mut matches.iter().copied());
+            }
+        },
+        _ => {
+            for row in &left.rows {
+                let matches = right.rows.iter().filter(|right_row| row[left_key] != WordType::Null && right_row[right_key] == row[left_key]);
+                push_matches(row, &mut matches.into_iter());
+            }
+        },
+    }
+    rows
+}
+
+/// Rows chosen by `select`, copied into a temp table only when the result is needed
+struct Selection {
+    table_idx: usize,
+    cols: Vec<usize>,
+    conditions: Vec<Condition>,
+    offset: usize,
+    limit: Option<usize>,
+}
+
+impl Selection {
+    fn collect(self, database: &Database) -> Result<Table, Error> {
+        let source = &database.tables[self.table_idx];
+        let mut table = Table {
+            schema: TableSchema {
+                name: String::from("temp"),
+                cols: self.cols.iter().map(|idx| source.schema.cols[*idx].clone()).collect(),
+                indexes: vec![],
+                sequence: 1,
+            },
+            rows: vec![],
+        };
+
+        if self.limit == Some(0) {
+            return Ok(table);
+        }
+
+        // Returns false when enough rows are collected
+        let mut skipped = 0;
+        let mut visit = |row: &Row| {
+            if !self.conditions.is_empty() && !check_conditions(&self.conditions, row) {
+                return true;
+            }
+            if skipped < self.offset {
+                skipped += 1;
+                return true;
+            }
+            table.rows.push(self.cols.iter().map(|idx| row[*idx].clone()).collect());
+            self.limit != Some(table.rows.len())
+        };
+        // Pages of a stored table are read only up to the last candidate row
+        let candidates = index_candidates(source, &self.conditions);
+        scan_rows(database, self.table_idx, candidates.as_deref(), |_, row| visit(row))?;
+        Ok(table)
+    }
+}
+
+/// Aggregate function over a column of the selected rows, `count` has no column
+struct AggregateSpec {
+    aggregate: Aggregate,
+    col: Option<usize>,
+    pos: usize,
+}
+
+fn aggregate_cols(table: &Table, aggregates: &[AggregateSpec]) -> Vec<Col> {
+    let mut cols = vec![];
+    for AggregateSpec {aggregate, col, ..} in aggregates {
+        cols.push(match col {
+            Some(idx) => {
+                let col = &table.schema.cols[*idx];
+                let data_type = match aggregate {
+                    Aggregate::Count => DataType::Int,
+                    Aggregate::Avg => DataType::Float,
+                    _ => col.data_type,
+                };
+                Col::plain(format!("{}-{}", aggregate.name(), col.name), data_type, true)
+            },
+            None => Col::plain(aggregate.name().to_string(), DataType::Int, false),
+        });
+    }
+    cols
+}
+
+/// Computes values of the aggregates over the rows, skipping NULL values
+fn aggregate_rows(rows: &[&Row], aggregates: &[AggregateSpec]) -> Result<Row, Error> {
+    let mut values = vec![];
+    for AggregateSpec {aggregate, col, pos} in aggregates {
+        let idx = match col {
+            Some(idx) => *idx,
+            None => {
+                values.push(WordType::Int(rows.len() as i32));
+                continue;
+            },
+        };
+        let mut col_values = rows.iter().map(|row| &row[idx]).filter(|value| **value != WordType::Null).peekable();
+        if col_values.peek().is_none() {
+            values.push(WordType::Null);
+            continue;
+        }
+        let value = match aggregate {
+            Aggregate::Sum | Aggregate::Avg => {
+                let overflow = || Error::IntegerOverflow {op: aggregate.name(), pos: *pos};
+                let mut int_sum: i128 = 0;
+                let mut float_sum: f64 = 0.0;
+                let mut count: i128 = 0;
+                let first = *col_values.peek().unwrap();
+                for value in col_values {
+                    match value {
+                        WordType::Int(value) => int_sum += *value as i128,
+                        WordType::BigInt(value) => int_sum += *value as i128,
+                        WordType::Float(Float(value)) => float_sum += value,
+                        _ => unreachable!(),
+                    }
+                    count += 1;
+                }
+                if *aggregate == Aggregate::Avg {
+                    WordType::Float(Float((int_sum as f64 + float_sum) / count as f64))
+                } else {
+                    match first {
+                        WordType::Int(_) => WordType::Int(i32::try_from(int_sum).map_err(|_| overflow())?),
+                        WordType::BigInt(_) => WordType::BigInt(i64::try_from(int_sum).map_err(|_| overflow())?),
+                        WordType::Float(_) => WordType::Float(Float(float_sum)),
+                        _ => unreachable!(),
+                    }
+                }
+            },
+            Aggregate::Min => col_values.min_by(|a, b| compare_words(a, b)).unwrap().clone(),
+            Aggregate::Max => col_values.max_by(|a, b| compare_words(a, b)).unwrap().clone(),
+            Aggregate::Count => unreachable!(),
+        };
+        values.push(value);
+    }
+    Ok(values)
+}
+
+/// Replaces the selected rows with one row of aggregates over all of them
+fn aggregate_table(table: Table, aggregates: &[AggregateSpec]) -> Result<Table, Error> {
+    let rows = table.rows.iter().collect::<Vec<&Row>>();
+    let row = aggregate_rows(&rows, aggregates)?;
+    Ok(Table {
+        schema: TableSchema {
+            name: String::from("temp"),
+            cols: aggregate_cols(&table, aggregates),
+            indexes: vec![],
+            sequence: 1,
+        },
+        rows: vec![row],
+    })
+}
+
+/// Columns of a table produced by a query, `(name, data_type, nullable)` for every column
+fn result_cols(cols: &[(&str, DataType, bool)]) -> Vec<Col> {
+    cols.iter().map(|(name, data_type, nullable)| Col::plain(name.to_string(), *data_type, *nullable)).collect()
+}
+
+/// Name, row count, column and index counts and size of the table file of every table
+fn tables_table(database: &Database) -> Result<Table, Error> {
+    let mut rows = vec![];
+    for (table_idx, table) in database.tables.iter().enumerate() {
+        rows.push(vec![
+            WordType::Str(table.schema.name.clone()),
+            WordType::BigInt(row_count(database, table_idx) as i64),
+            WordType::BigInt(table.schema.cols.len() as i64),
+            WordType::BigInt(table.schema.indexes.len() as i64),
+            WordType::BigInt(table_file_size(database, table_idx)? as i64),
+        ]);
+    }
+    Ok(Table {
+        schema: TableSchema {
+            name: String::from("tables"),
+            cols: result_cols(&[
+                ("name", DataType::Str, false),
+                ("rows", DataType::BigInt, false),
+                ("columns", DataType::BigInt, false),
+                ("indexes", DataType::BigInt, false),
+                ("size", DataType::BigInt, false),
+            ]),
+            indexes: vec![],
+            sequence: 1,
+        },
+        rows,
+    })
+}
+
+/// Name, type and constraints of every column of the table
+fn describe_table(table: &Table) -> Table {
+    let optional = |value: Option<String>| value.map_or(WordType::Null, WordType::Str);
+    let mut rows = vec![];
+    for col in &table.schema.cols {
+        let mut data_type = data_type_to_string(col.data_type);
+        if let Some(max_len) = col.max_len {
+            data_type.push_str(&format!("({max_len})"));
+        }
+        let constraint = col.constraint.map(|constraint| match constraint {
+            Constraint::PrimaryKey => "primary".to_string(),
+            Constraint::Unique => "unique".to_string(),
+        });
+        let references = col.foreign_key.as_ref().map(|ForeignKey {table, col, on_delete}| match on_delete {
+            OnDelete::Restrict => format!("{table}.{col}"),
+            OnDelete::Cascade => format!("{table}.{col},cascade"),
+        });
+        let index = table.schema.indexes.iter().find(|index| col.name == table.schema.cols[index.col].name);
+        rows.push(vec![
+            WordType::Str(col.name.clone()),
+            WordType::Str(data_type),
+            WordType::Bool(col.nullable),
+            optional(constraint),
+            optional(references),
+            optional(col.default.as_ref().map(value_to_literal)),
+            WordType::Bool(col.auto_increment),
+            optional(index.map(|index| index.name.clone())),
+        ]);
+    }
+    Table {
+        schema: TableSchema {
+            name: table.schema.name.clone(),
+            cols: result_cols(&[
+                ("name", DataType::Str, false),
+                ("type", DataType::Str, false),
+                ("nullable", DataType::Bool, false),
+                ("constraint", DataType::Str, true),
+                ("references", DataType::Str, true),
+                ("default", DataType::Str, true),
+                ("auto", DataType::Bool, false),
+                ("index", DataType::Str, true),
+            ]),
+            indexes: vec![],
+            sequence: 1,
+        },
+        rows,
+    }
+}
+
+/// Path, format and table counts of the database with sizes of its files and of its log
+fn info_table(database: &Database) -> Result<Table, Error> {
+    let log_path = database.format.log_path(&database.path);
+    // Database opened in memory has no files
+    let log_size = if database.path.is_empty() { 0 } else { wal::size(&log_path) };
+    let (format, size) = match database.format {
+        _ if database.path.is_empty() => ("memory", 0),
+        Format::Directory => {
+            // The log is reported separately
+            let mut size = 0;
+            for entry in fs::read_dir(&database.path).map_err(io_error("read", &database.path))? {
+                let entry = entry.map_err(io_error("read", &database.path))?;
+                if entry.path() != Path::new(&log_path) {
+                    size += entry.metadata().map_err(io_error("read", &database.path))?.len();
+                }
+            }
+            ("directory", size)
+        },
+        Format::SingleFile => {
+            ("file", fs::metadata(&database.path).map_err(io_error("read", &database.path))?.len())
+        },
+    };
+    Ok(Table {
+        schema: TableSchema {
+            name: String::from("info"),
+            cols: result_cols(&[
+                ("path", DataType::Str, false),
+                ("format", DataType::Str, false),
+                ("tables", DataType::BigInt, false),
+                ("quarantined", DataType::BigInt, false),
+                ("size", DataType::BigInt, false),
+                ("log", DataType::BigInt, false),
+            ]),
+            indexes: vec![],
+            sequence: 1,
+        },
+        rows: vec![vec![
+            WordType::Str(database.path.clone()),
+            WordType::Str(format.to_string()),
+            WordType::BigInt(database.tables.len() as i64),
+            WordType::BigInt(database.quarantined.len() as i64),
+            WordType::BigInt(size as i64),
+            WordType::BigInt(log_size as i64),
+        ]],
+    })
+}
+
+/// Executes the query as a part of the current transaction
+fn execute_query(query: &str, database: &mut Database) -> Result<Option<Table>, Error> {
+    if database.transaction.is_none() {
+        database.transaction = Some(Transaction::new(false));
+    }
+
+    match execute_ops(query, database) {
+        Ok(table) => {
+            if let Some(Transaction {explicit: false, ..}) = database.transaction {
+                commit_transaction(database)?;
+            }
+            Ok(table)
+        },
+        Err(err) => {
+            if let Some(transaction) = database.transaction.take() {
+                rollback_transaction(database, transaction);
+            }
+            Err(err)
+        },
+    }
+}
+
+/// Makes type words outside schemas of `create` and `add-column` strings
+fn resolve_type_words(query: &mut [Token], source: &str) {
+    let mut in_schema = vec![false; query.len()];
+    for i in 0..query.len() {
+        let types = match query[i].op {
+            Op::Create => usize::MAX,
+            Op::AddColumn => 1,
+            _ => continue,
+        };
+        // Words before the operation are pairs of a column name and its type followed by the table name
+        let words = query[..i].iter().rev().take_while(|token| matches!(token.op, Op::PushWord {..})).count();
+        for distance in (1..words).step_by(2).take(types) {
+            in_schema[i - 1 - distance] = true;
+        }
+    }
+    for (token, in_schema) in query.iter_mut().zip(in_schema) {
+        if let (Op::PushWord {word_type: WordType::Type(_), ..}, false) = (&token.op, in_schema) {
+            let word = source[token.pos..].split(char::is_whitespace).next().unwrap();
+            token.op = Op::PushWord {data_type: DataType::Str, word_type: WordType::Str(word.to_string())};
+        }
+    }
+}
+
+/// Makes names of aggregate results used in conditions, like `count` in `count 1 > having`, columns
+fn resolve_condition_columns(query: &mut [Token]) {
+    for i in 0..query.len() {
+        let aggregate = match query[i].op {
+            Op::Aggregate(aggregate) => aggregate,
+            _ => continue,
+        };
+        let is_column = match query.get(i + 1).map(|token| &token.op) {
+            Some(Op::IsNull | Op::IsNotNull) => true,
+            Some(Op::PushWord {..}) => matches!(
+                query.get(i + 2).map(|token| &token.op),
+                Some(Op::Equal | Op::NotEqual | Op::Less | Op::More),
+            ),
+            _ => false,
+        };
+        if is_column {
+            let name = aggregate.name().to_string();
+            query[i].op = Op::PushWord {data_type: DataType::Str, word_type: WordType::Str(name)};
+        }
+    }
+}
+
+fn execute_ops(query: &str, database: &mut Database) -> Result<Option<Table>, Error> {
+    let source = query;
+    let mut query = parse_query(query)?;
+    resolve_type_words(&mut query, source);
+    resolve_condition_columns(&mut query);
+
+    let mut words: Vec<Word> = vec![];
+    let mut conditions: Vec<RawCondition> = vec![];
+    let mut temp_table = None;
+    // Operation which produced the temporary table
+    let mut temp_op = None;
+    let mut selection: Option<Selection> = None;
+    let mut aggregates: Vec<AggregateSpec> = vec![];
+    for token in query {
+        let pos = token.pos;
+        // Any other operation may need or change the selected rows, so they are copied first
+        if !matches!(token.op, Op::PushWord {..} | Op::Limit | Op::Offset) {
+            if let Some(selection) = selection.take() {
+                temp_table = Some(selection.collect(database)?);
+            }
+        }
+        if !aggregates.is_empty() && !matches!(token.op, Op::PushWord {..} | Op::Aggregate(_) | Op::GroupBy) {
+            temp_table = Some(aggregate_table(temp_table.take().unwrap(), &aggregates)?);
+            aggregates.clear();
+        }
+        match token.op {
+            Op::Select => {
+                let table_idx = table_idx_for(&token, words.pop(), database)?;
+                
+                let row_idxs = select_cols(&mut words, &database.tables[table_idx], &token)?;
+
+                let comp_conds = compile_conditions(&conditions, &database.tables[table_idx], pos)?;
+                conditions.clear();
+                temp_op = Some(Op::Select);
+                selection = Some(Selection {
+                    table_idx,
+                    cols: row_idxs,
+                    conditions: comp_conds,
+                    offset: 0,
+                    limit: None,
+                });
+            },
+            op @ Op::Limit | op @ Op::Offset => {
+                let count = match words.pop() {
+                    Some(Word {value: WordType::Int(count), ..}) if count >= 0 => count as usize,
+                    Some(word) => return Err(Error::UnexpectedWord {expected: "row count", found: word.value, pos: word.pos}),
+                    None => return Err(Error::StackUnderflow {op: op.name(), needed: 1, found: 0, pos}),
+                };
+
+                // Trimming the selection before the scan lets it stop early
+                if let Some(selection) = selection.as_mut() {
+                    if op == Op::Limit {
+                        selection.limit = Some(selection.limit.map_or(count, |limit| limit.min(count)));
+                    } else {
+                        selection.offset += count;
+                        selection.limit = selection.limit.map(|limit| limit.saturating_sub(count));
+                    }
+                    continue;
+                }
+
+                let table = match temp_table.as_mut() {
+                    Some(table) => table,
+                    None => return Err(Error::MissingSelect {op: op.name(), pos}),
+                };
+                if op == Op::Limit {
+                    table.rows.truncate(count);
+                } else {
+                    table.rows.drain(..count.min(table.rows.len()));
+                }
+            },
+            Op::Insert => {
+                let table_idx = table_idx_for(&token, words.pop(), database)?;
+                let table = &database.tables[table_idx];
+                let cols = &table.schema.cols;
+                let mut row = vec![];
+                for i in (0..cols.len()).rev() {
+                    match words.pop() {
+                        // `null` in an auto-increment column takes the next value of the sequence
+                        Some(word) if cols[i].auto_increment && word.value == WordType::Null => row.push(next_sequence_value(table, word.pos)?),
+                        Some(word) => row.push(word),
+                        None => return Err(Error::StackUnderflow {op: "insert", needed: cols.len(), found: cols.len() - i - 1, pos}),
+                    }
+                }
+                row.reverse();
+                insert_row(database, table_idx, row)?;
+            },
+            Op::InsertNamed => {
+                let table_idx = table_idx_for(&token, words.pop(), database)?;
+                let table = &database.tables[table_idx];
+                let cols = &table.schema.cols;
+
+                let mut values = vec![None; cols.len()];
+                while words.len() >= 2 {
+                    let value = words.pop().unwrap();
+                    let col = words.pop().unwrap();
+                    let col_name = match col.value {
+                        WordType::Str(name) => name,
+                        other => return Err(Error::UnexpectedWord {expected: "column name", found: other, pos: col.pos}),
+                    };
+                    match cols.iter().position(|Col {name, ..}| *name == col_name) {
+                        Some(idx) if values[idx].is_some() => {
+                            return Err(Error::ColumnExists {table: table.schema.name.clone(), column: col_name, pos: col.pos});
+                        },
+                        Some(idx) => values[idx] = Some(value),
+                        None => return Err(Error::UnknownColumn {table: table.schema.name.clone(), column: col_name, pos: col.pos}),
+                    }
+                }
+                if !words.is_empty() {
+                    return Err(Error::StackUnderflow {op: "insert-named", needed: 2, found: 1, pos});
+                }
+                if values.iter().all(Option::is_none) {
+                    return Err(Error::MissingColumns {op: "insert-named", pos});
+                }
+
+                // Omitted columns have the default value, the next value of the sequence or NULL
+                let mut row = vec![];
+                for (col, value) in cols.iter().zip(values) {
+                    row.push(match (value, &col.default) {
+                        (Some(value), _) => value,
+                        (None, Some(default)) => {
+                            let data_type = if *default == WordType::Null { DataType::Null } else { col.data_type };
+                            Word {data_type, value: default.clone(), pos}
+                        },
+                        (None, None) if col.auto_increment => next_sequence_value(table, pos)?,
+                        (None, None) if col.nullable => Word {data_type: DataType::Null, value: WordType::Null, pos},
+                        (None, None) => return Err(Error::MissingValue {table: table.schema.name.clone(), column: col.name.clone(), pos}),
+                    });
+                }
+                insert_row(database, table_idx, row)?;
+            },
+            Op::Delete => {
+                let table_idx = table_idx_for(&token, words.pop(), database)?;
+                
+                let comp_conds = compile_conditions(&conditions, &database.tables[table_idx], pos)?;
+                if comp_conds.is_empty() {
+                    return Err(Error::InvalidConditions {count: 0, pos});
+                }
+                let rows_to_delete = matching_rows(database, table_idx, &comp_conds)?;
+
+                conditions.clear();
+                delete_rows(database, table_idx, rows_to_delete, pos)?;
+            },
+            Op::Update => {
+                let table_idx = table_idx_for(&token, words.pop(), database)?;
+                let table = &database.tables[table_idx];
+
+                let mut assignments = vec![];
+                while words.len() >= 2 {
+                    let value = words.pop().unwrap();
+                    let col = words.pop().unwrap();
+                    let col_name = match col.value {
+                        WordType::Str(name) => name,
+                        other => return Err(Error::UnexpectedWord {expected: "column name", found: other, pos: col.pos}),
+                    };
+                    let idx = match table.schema.cols.iter().position(|Col {name, ..}| *name == col_name) {
+                        Some(idx) => idx,
+                        None => return Err(Error::UnknownColumn {table: table.schema.name.clone(), column: col_name, pos: col.pos}),
+                    };
+                    check_value(&table.schema.cols[idx], &value)?;
+                    assignments.push((idx, value));
+                }
+                if !words.is_empty() {
+                    return Err(Error::StackUnderflow {op: "update", needed: 2, found: 1, pos});
+                }
+                if assignments.is_empty() {
+                    return Err(Error::MissingColumns {op: "update", pos});
+                }
+                assignments.reverse();
+
+                let comp_conds = compile_conditions(&conditions, table, pos)?;
+                conditions.clear();
+                let rows_to_update = matching_rows(database, table_idx, &comp_conds)?;
+                let table_name = table.schema.name.clone();
+                if !rows_to_update.is_empty() {
+                    for (idx, value) in &assignments {
+                        check_unique(database, table_idx, *idx, value, &rows_to_update)?;
+                        check_parent(database, &table.schema.cols[*idx], value)?;
+                        check_not_referenced(database, table_idx, *idx, &rows_to_update, value)?;
+                    }
+                }
+
+                let updated = rows_to_update.len();
+                let record = LogRecord::Update {
+                    table: table_name,
+                    rows: rows_to_update,
+                    values: assignments.into_iter().map(|(idx, value)| (idx, value.value)).collect(),
+                };
+                log_and_apply(database, record)?;
+
+                temp_op = Some(Op::Update);
+                temp_table = Some(Table {
+                    schema: TableSchema {
+                        name: String::from("temp"),
+                        cols: vec![Col::plain("updated".to_string(), DataType::Int, false)],
+                        indexes: vec![],
+                        sequence: 1,
+                    },
+                    rows: vec![vec![WordType::Int(updated as i32)]],
+                });
+            },
+            op @ Op::Equal | op @ Op::NotEqual | op @ Op::Less | op @ Op::More => {
+                assert!(Op::Count.as_u8() == 36, "Exhaustive Op handling in logical_op_check()");
+                if words.len() < 2 {
+                    return Err(Error::StackUnderflow {op: op.name(), needed: 2, found: words.len(), pos});
+                }
+
+                let value = words.pop().unwrap();
+                let col = words.pop().unwrap();
+                conditions.push(RawCondition {op, operands: Some((col, value)), pos});
+            },
+            op @ Op::And | op @ Op::Or => {
+                conditions.push(RawCondition {op, operands: None, pos});
+            },
+            op @ Op::IsNull | op @ Op::IsNotNull => {
+                let col = match words.pop() {
+                    Some(col) => col,
+                    None => return Err(Error::StackUnderflow {op: op.name(), needed: 1, found: 0, pos}),
+                };
+                let value = Word {data_type: DataType::Null, value: WordType::Null, pos};
+                conditions.push(RawCondition {op, operands: Some((col, value)), pos});
+            },
+            Op::Create => {
+                let table_name = match words.pop() {
+                    Some(word) => new_table_name_check(word, database)?,
+                    None => return Err(Error::StackUnderflow {op: "create", needed: 1, found: 0, pos}),
+                };
+
+                // After `select` the table is created from its columns and rows, results of other
+                // operations are dropped and the table is created from the schema words
+                let from_select = matches!(temp_op, Some(Op::Select | Op::Join | Op::LeftJoin | Op::GroupBy));
+                if let (Some(table), true) = (temp_table.take(), from_select) {
+                    let mut cols: Vec<Col> = vec![];
+                    for col in table.schema.cols {
+                        if cols.iter().any(|other| other.name == col.name) {
+                            return Err(Error::ColumnExists {table: table_name, column: col.name, pos});
+                        }
+                        // Constraints stay with the source table
+                        cols.push(Col {constraint: None, foreign_key: None, auto_increment: false, default: None, ..col});
+                    }
+                    let schema = TableSchema {
+                        name: table_name.clone(),
+                        cols,
+                        indexes: vec![],
+                        sequence: 1,
+                    };
+                    log_and_apply(database, LogRecord::Create {schema})?;
+                    for row in table.rows {
+                        log_and_apply(database, LogRecord::Insert {table: table_name.clone(), row})?;
+                    }
+                    continue;
+                }
+
+                let mut cols = vec![];
+                let mut type_positions = vec![];
+                while let Some(word) = words.pop() {
+                    type_positions.push(word.pos);
+                    let col_type = match word.value {
+                        WordType::Type(col_type) => col_type,
+                        _ => {
+                            words.push(word);
+                            break;
+                        },
+                    };
+                    let col_name = match words.pop() {
+                        Some(word) => {
+                            match word.value {
+                                WordType::Str(name) => name,
+                                other => return Err(Error::UnexpectedWord {expected: "column name", found: other, pos: word.pos}),
+                            }
+                        },
+                        None => return Err(Error::StackUnderflow {op: "create", needed: 2, found: 1, pos}),
+                    };
+                    if col_type.data_type == DataType::Type {
+                        return Err(Error::UnsupportedType {table: table_name, column: col_name});
+                    }
+                    cols.push(Col::new(col_name, col_type));
+                }
+                if cols.iter().filter(|col| col.constraint == Some(Constraint::PrimaryKey)).count() > 1 {
+                    return Err(Error::MultiplePrimaryKeys {table: table_name, pos});
+                }
+                if cols.iter().filter(|col| col.auto_increment).count() > 1 {
+                    return Err(Error::MultipleAutoIncrements {table: table_name, pos});
+                }
+
+                let schema = TableSchema {
+                    name: table_name,
+                    cols: cols.into_iter().rev().collect(),
+                    indexes: vec![],
+                    sequence: 1,
+                };
+                for (col, pos) in schema.cols.iter().zip(type_positions.iter().rev()) {
+                    check_foreign_key(database, &schema, col, *pos)?;
+                }
+                let record = LogRecord::Create {schema};
+                log_and_apply(database, record)?;
+            },
+            Op::Drop => {
+                let table_idx = table_idx_for(&token, words.pop(), database)?;
+                drop_references(database, table_idx, pos)?;
+                
+                // Files of the table are removed by the next checkpoint
+                let record = LogRecord::Drop {
+                    table: database.tables[table_idx].schema.name.clone(),
+                };
+                log_and_apply(database, record)?;
+            },
+            Op::Rename => {
+                let name = match words.pop() {
+                    Some(word) => new_table_name_check(word, database)?,
+                    None => return Err(Error::StackUnderflow {op: "rename", needed: 2, found: 0, pos}),
+                };
+                let table = match words.pop() {
+                    Some(word) => table_name_check(word, database)?,
+                    None => return Err(Error::StackUnderflow {op: "rename", needed: 2, found: 1, pos}),
+                };
+
+                // Files of the table are moved by the next checkpoint
+                let record = LogRecord::RenameTable {
+                    table: database.tables[table].schema.name.clone(),
+                    name,
+                };
+                log_and_apply(database, record)?;
+            },
+            Op::Tables => {
+                temp_op = Some(Op::Tables);
+                temp_table = Some(tables_table(database)?);
+            },
+            Op::Describe => {
+                let table_idx = table_idx_for(&token, words.pop(), database)?;
+                temp_op = Some(Op::Describe);
+                temp_table = Some(describe_table(&database.tables[table_idx]));
+            },
+            Op::Info => {
+                temp_op = Some(Op::Info);
+                temp_table = Some(info_table(database)?);
+            },
+            Op::AddColumn => {
+                let table_idx = table_idx_for(&token, words.pop(), database)?;
+                let table = &database.tables[table_idx];
+                let (col_type, type_pos) = match words.pop() {
+                    Some(Word {value: WordType::Type(col_type), pos, ..}) => (col_type, pos),
+                    Some(word) => return Err(Error::UnexpectedWord {expected: "column type", found: word.value, pos: word.pos}),
+                    None => return Err(Error::StackUnderflow {op: "add-column", needed: 3, found: 1, pos}),
+                };
+                let (name, _) = col_name_check(words.pop(), table, true, &token)?;
+                if col_type.data_type == DataType::Type {
+                    return Err(Error::UnsupportedType {table: table.schema.name.clone(), column: name});
+                }
+                let col = Col::new(name, col_type);
+                if col.constraint == Some(Constraint::PrimaryKey) && has_primary_key(&table.schema.cols) {
+                    return Err(Error::MultiplePrimaryKeys {table: table.schema.name.clone(), pos});
+                }
+                if col.auto_increment && table.schema.cols.iter().any(|col| col.auto_increment) {
+                    return Err(Error::MultipleAutoIncrements {table: table.schema.name.clone(), pos});
+                }
+                check_foreign_key(database, &table.schema, &col, type_pos)?;
+
+                // Value of existing rows must fit the column
+                let row_count = row_count(database, table_idx);
+                if col.auto_increment && row_count > 0 && i32::try_from(table.schema.sequence + row_count as i64 - 1).is_err() {
+                    return Err(Error::IntegerOverflow {op: "add-column", pos});
+                }
+                if row_count > 0 && !col.auto_increment {
+                    let value = match &col.default {
+                        Some(default) => default.clone(),
+                        None if col.nullable => WordType::Null,
+                        None => return Err(Error::MissingValue {table: table.schema.name.clone(), column: col.name, pos}),
+                    };
+                    if col.constraint.is_some() && value != WordType::Null && row_count > 1 {
+                        return Err(Error::DuplicateValue {table: table.schema.name.clone(), column: col.name, value, pos});
+                    }
+                    check_parent(database, &col, &Word {data_type: col.data_type, value, pos})?;
+                }
+
+                let record = LogRecord::AddColumn {
+                    table: table.schema.name.clone(),
+                    col,
+                };
+                log_and_apply(database, record)?;
+            },
+            Op::DropColumn => {
+                let table_idx = table_idx_for(&token, words.pop(), database)?;
+                let table = &database.tables[table_idx];
+                let (name, col) = col_name_check(words.pop(), table, false, &token)?;
+                let children = referencing_cols(database, table_idx, col);
+                if let Some((child_idx, _)) = children.into_iter().find(|child| *child != (table_idx, col)) {
+                    return Err(Error::ColumnReferenced {
+                        table: table.schema.name.clone(),
+                        column: name,
+                        child: database.tables[child_idx].schema.name.clone(),
+                        pos,
+                    });
+                }
+
+                let record = LogRecord::DropColumn {
+                    table: table.schema.name.clone(),
+                    col,
+                };
+                log_and_apply(database, record)?;
+            },
+            Op::RenameColumn => {
+                let table_idx = table_idx_for(&token, words.pop(), database)?;
+                let table = &database.tables[table_idx];
+                let (name, _) = col_name_check(words.pop(), table, true, &token)?;
+                let (_, col) = col_name_check(words.pop(), table, false, &token)?;
+
+                let record = LogRecord::RenameColumn {
+                    table: table.schema.name.clone(),
+                    col,
+                    name,
+                };
+                log_and_apply(database, record)?;
+            },
+            Op::CreateIndex => {
+                let table_idx = table_idx_for(&token, words.pop(), database)?;
+                let table = &database.tables[table_idx];
+                if words.len() < 2 {
+                    return Err(Error::StackUnderflow {op: "create-index", needed: 3, found: words.len() + 1, pos});
+                }
+                let word = words.pop().unwrap();
+                let col = match word.value {
+                    WordType::Str(name) => match table.schema.cols.iter().position(|Col {name: col_name, ..}| *col_name == name) {
+                        Some(idx) => idx,
+                        None => return Err(Error::UnknownColumn {table: table.schema.name.clone(), column: name, pos: word.pos}),
+                    },
+                    other => return Err(Error::UnexpectedWord {expected: "column name", found: other, pos: word.pos}),
+                };
+                let data_type = table.schema.cols[col].data_type;
+                if !matches!(data_type, DataType::Int | DataType::Str) {
+                    let column = table.schema.cols[col].name.clone();
+                    return Err(Error::NotIndexable {table: table.schema.name.clone(), column, found: data_type, pos: word.pos});
+                }
+                let name = words.pop().unwrap();
+                let name = match name.value {
+                    WordType::Str(index) if table.schema.indexes.iter().any(|Index {name, ..}| *name == index) => {
+                        return Err(Error::IndexExists {table: table.schema.name.clone(), index, pos: name.pos});
+                    },
+                    WordType::Str(index) => index,
+                    other => return Err(Error::UnexpectedWord {expected: "index name", found: other, pos: name.pos}),
+                };
+
+                let record = LogRecord::CreateIndex {
+                    table: table.schema.name.clone(),
+                    name,
+                    col,
+                };
+                log_and_apply(database, record)?;
+            },
+            Op::DropIndex => {
+                let table_idx = table_idx_for(&token, words.pop(), database)?;
+                let table = &database.tables[table_idx];
+                let name = match words.pop() {
+                    Some(Word {value: WordType::Str(index), pos, ..}) => {
+                        if !table.schema.indexes.iter().any(|Index {name, ..}| *name == index) {
+                            return Err(Error::UnknownIndex {table: table.schema.name.clone(), index, pos});
+                        }
+                        index
+                    },
+                    Some(word) => return Err(Error::UnexpectedWord {expected: "index name", found: word.value, pos: word.pos}),
+                    None => return Err(Error::StackUnderflow {op: "drop-index", needed: 2, found: 1, pos}),
+                };
+
+                let record = LogRecord::DropIndex {
+                    table: table.schema.name.clone(),
+                    name,
+                };
+                log_and_apply(database, record)?;
+            },
+            Op::Begin => {
+                // Changes made earlier in the query become a part of the transaction,
+                // so an error later in the query still reverts them
+                match database.transaction.get_or_insert_with(|| Transaction::new(false)) {
+                    Transaction {explicit: true, ..} => return Err(Error::TransactionStarted {pos}),
+                    transaction => transaction.explicit = true,
+                }
+            },
+            Op::Commit => {
+                if !matches!(database.transaction, Some(Transaction {explicit: true, ..})) {
+                    return Err(Error::NoTransaction {op: "commit", pos});
+                }
+                commit_transaction(database)?;
+                database.transaction = Some(Transaction::new(false));
+            },
+            Op::Rollback => {
+                let transaction = match database.transaction.take() {
+                    Some(transaction) if transaction.explicit => transaction,
+                    _ => return Err(Error::NoTransaction {op: "rollback", pos}),
+                };
+                rollback_transaction(database, transaction);
+                database.transaction = Some(Transaction::new(false));
+            },
+            Op::OrderBy => {
+                let table = match temp_table.as_mut() {
+                    Some(table) => table,
+                    None => return Err(Error::MissingSelect {op: "order-by", pos}),
+                };
+
+                // Every key is a column name optionally followed by `asc` or `desc`
+                let mut keys = vec![];
+                while let Some(word) = words.pop() {
+                    let mut descending = false;
+                    let col = match word.value {
+                        WordType::Str(ref direction) if direction == "asc" || direction == "desc" => {
+                            descending = direction == "desc";
+                            match words.pop() {
+                                Some(col) => col,
+                                None => return Err(Error::StackUnderflow {op: "order-by", needed: 2, found: 1, pos}),
+                            }
+                        },
+                        WordType::Str(_) => word,
+                        _ => {
+                            words.push(word);
+                            break;
+                        },
+                    };
+                    let col_name = match col.value {
+                        WordType::Str(name) => name,
+                        other => return Err(Error::UnexpectedWord {expected: "column name", found: other, pos: col.pos}),
+                    };
+                    let idx = match table.schema.cols.iter().position(|Col {name, ..}| *name == col_name) {
+                        Some(idx) => idx,
+                        None => return Err(Error::UnknownColumn {table: table.schema.name.clone(), column: col_name, pos: col.pos}),
+                    };
+                    keys.push((idx, descending));
+                }
+                if keys.is_empty() {
+                    return Err(Error::MissingColumns {op: "order-by", pos});
+                }
+                keys.reverse();
+
+                // Stable sort keeps storage order of rows with equal keys
+                table.rows.sort_by(|a, b| {
+                    for (idx, descending) in &keys {
+                        let ordering = compare_words(&a[*idx], &b[*idx]);
+                        if ordering != Ordering::Equal {
+                            return if *descending { ordering.reverse() } else { ordering };
+                        }
+                    }
+                    Ordering::Equal
+                });
+            },
+            Op::Join | Op::LeftJoin => {
+                let mut key_cols = vec![];
+                for _ in 0..2 {
+                    match words.pop() {
+                        Some(Word {value: WordType::Str(name), pos, ..}) => key_cols.push((name, pos)),
+                        Some(word) => return Err(Error::UnexpectedWord {expected: "column name", found: word.value, pos: word.pos}),
+                        None => return Err(Error::StackUnderflow {op: token.op.name(), needed: 4, found: key_cols.len(), pos}),
+                    }
+                }
+                let right_idx = table_idx_for(&token, words.pop(), database)?;
+                let left_idx = table_idx_for(&token, words.pop(), database)?;
+                let (left, right) = (read_table(database, left_idx)?, read_table(database, right_idx)?);
+                let (left, right) = (left.as_ref(), right.as_ref());
+
+                let mut keys = vec![];
+                for (table, (col_name, col_pos)) in [right, left].into_iter().zip(key_cols) {
+                    match table.schema.cols.iter().position(|Col {name, ..}| *name == col_name) {
+                        Some(idx) => keys.push(idx),
+                        None => return Err(Error::UnknownColumn {table: table.schema.name.clone(), column: col_name, pos: col_pos}),
+                    }
+                }
+                let (right_key, left_key) = (keys[0], keys[1]);
+                let (left_type, right_type) = (left.schema.cols[left_key].data_type, right.schema.cols[right_key].data_type);
+                if left_type != right_type {
+                    return Err(Error::TypeMismatch {
+                        column: right.schema.cols[right_key].name.clone(),
+                        expected: left_type,
+                        found: right_type,
+                        pos,
+                    });
+                }
+
+                // Columns are qualified with names of their tables, so names don't collide
+                let mut cols = vec![];
+                for table in [left, right] {
+                    for col in &table.schema.cols {
+                        cols.push(Col {
+                            name: format!("{}.{}", table.schema.name, col.name),
+                            data_type: col.data_type,
+                            nullable: col.nullable || (token.op == Op::LeftJoin && std::ptr::eq(table, right)),
+                            max_len: col.max_len,
+                            constraint: None,
+                            foreign_key: None,
+                            auto_increment: false,
+                            default: None,
+                        });
+                    }
+                }
+                let joined = Table {
+                    schema: TableSchema {
+                        name: String::from("temp"),
+                        cols,
+                        indexes: vec![],
+                        sequence: 1,
+                    },
+                    rows: join_rows(left, right, left_key, right_key, token.op == Op::LeftJoin),
+                };
+
+                let row_idxs = select_cols(&mut words, &joined, &token)?;
+                let comp_conds = compile_conditions(&conditions, &joined, pos)?;
+                conditions.clear();
+                let mut table = Table {
+                    schema: TableSchema {
+                        name: String::from("temp"),
+                        cols: row_idxs.iter().map(|idx| joined.schema.cols[*idx].clone()).collect(),
+                        indexes: vec![],
+                        sequence: 1,
+                    },
+                    rows: vec![],
+                };
+                for row in &joined.rows {
+                    if comp_conds.is_empty() || check_conditions(&comp_conds, row) {
+                        table.rows.push(row_idxs.iter().map(|idx| row[*idx].clone()).collect());
+                    }
+                }
+                temp_op = Some(token.op);
+                temp_table = Some(table);
+            },
+            Op::GroupBy => {
+                let table = match temp_table.take() {
+                    Some(table) => table,
+                    None => return Err(Error::MissingSelect {op: "group-by", pos}),
+                };
+
+                let mut keys = vec![];
+                while let Some(word) = words.pop() {
+                    let col_name = match word.value {
+                        WordType::Str(ref name) => name,
+                        _ => {
+                            words.push(word);
+                            break;
+                        },
+                    };
+                    match table.schema.cols.iter().position(|Col {name, ..}| name == col_name) {
+                        Some(idx) => keys.push(idx),
+                        None => return Err(Error::UnknownColumn {table: table.schema.name.clone(), column: col_name.clone(), pos: word.pos}),
+                    }
+                }
+                if keys.is_empty() {
+                    return Err(Error::MissingColumns {op: "group-by", pos});
+                }
+                keys.reverse();
+
+                // Groups are kept in order of their first rows
+                let mut groups: Vec<(Row, Vec<&Row>)> = vec![];
+                let mut group_idxs: HashMap<Row, usize> = HashMap::new();
+                for row in &table.rows {
+                    let key = keys.iter().map(|idx| row[*idx].clone()).collect::<Row>();
+                    match group_idxs.get(&key) {
+                        Some(idx) => groups[*idx].1.push(row),
+                        None => {
+                            group_idxs.insert(key.clone(), groups.len());
+                            groups.push((key, vec![row]));
+                        },
+                    }
+                }
+
+                let mut cols = keys.iter().map(|idx| table.schema.cols[*idx].clone()).collect::<Vec<Col>>();
+                cols.append(&mut aggregate_cols(&table, &aggregates));
+                let mut grouped = Table {
+                    schema: TableSchema {
+                        name: String::from("temp"),
+                        cols,
+                        indexes: vec![],
+                        sequence: 1,
+                    },
+                    rows: vec![],
+                };
+                for (mut key, rows) in groups {
+                    key.append(&mut aggregate_rows(&rows, &aggregates)?);
+                    grouped.rows.push(key);
+                }
+                aggregates.clear();
+                temp_op = Some(Op::GroupBy);
+                temp_table = Some(grouped);
+            },
+            Op::Having => {
+                let table = match temp_table.as_mut() {
+                    Some(table) => table,
+                    None => return Err(Error::MissingSelect {op: "having", pos}),
+                };
+                let comp_conds = compile_conditions(&conditions, table, pos)?;
+                if comp_conds.is_empty() {
+                    return Err(Error::InvalidConditions {count: 0, pos});
+                }
+                conditions.clear();
+                table.rows.retain(|row| check_conditions(&comp_conds, row));
+            },
+            Op::Aggregate(aggregate) => {
+                let table = match temp_table.as_ref() {
+                    Some(table) => table,
+                    None => return Err(Error::MissingSelect {op: aggregate.name(), pos}),
+                };
+
+                let mut col = None;
+                if aggregate != Aggregate::Count {
+                    let word = match words.pop() {
+                        Some(word) => word,
+                        None => return Err(Error::StackUnderflow {op: aggregate.name(), needed: 1, found: 0, pos}),
+                    };
+                    let col_name = match word.value {
+                        WordType::Str(name) => name,
+                        other => return Err(Error::UnexpectedWord {expected: "column name", found: other, pos: word.pos}),
+                    };
+                    let idx = match table.schema.cols.iter().position(|Col {name, ..}| *name == col_name) {
+                        Some(idx) => idx,
+                        None => return Err(Error::UnknownColumn {table: table.schema.name.clone(), column: col_name, pos: word.pos}),
+                    };
+                    let data_type = table.schema.cols[idx].data_type;
+                    let numeric = matches!(data_type, DataType::Int | DataType::BigInt | DataType::Float);
+                    if matches!(aggregate, Aggregate::Sum | Aggregate::Avg) && !numeric {
+                        return Err(Error::NotNumeric {op: aggregate.name(), column: col_name, found: data_type, pos: word.pos});
+                    }
+                    col = Some(idx);
+                }
+                aggregates.push(AggregateSpec {aggregate, col, pos});
+            },
+            Op::PushWord{data_type, word_type} => {
+                words.push(Word {data_type, value: word_type, pos}); 
+            },
+            Op::Count => unreachable!(),
+        }
+    }
+
+    if !words.is_empty() {
+        database.warnings.push(Warning::UnusedWords {count: words.len()});
+    }
+    if !conditions.is_empty() {
+        database.warnings.push(Warning::UnusedConditions {count: conditions.len()});
+    }
+    
+    if let Some(selection) = selection {
+        temp_table = Some(selection.collect(database)?);
+    }
+    if !aggregates.is_empty() {
+        temp_table = Some(aggregate_table(temp_table.take().unwrap(), &aggregates)?);
+    }
+    Ok(temp_table)
+}
+
+/// Opens the table file of a table in the directory `dir`, rows stay in its pages
+fn read_from_file(dir: &str, schema: TableSchema, pool: &PagePool) -> Result<(Table, Option<StoredRows>), Error> {
+    let mut table = Table {
+        schema,
+        rows: vec![],
+    };
+    
+    // File of a table without rows is written by the next checkpoint
+    let file_path = format!("{}/{}.tbl", dir, table.schema.name);
+    if !Path::new(&file_path).exists() {
+        return Ok((table, None));
+    }
+    let pager = Rc::new(Pager::open(&file_path, pool)?);
+    if let Some(stored) = open_stored_rows(pager, Layout::Plain, 0, &table.schema)? {
+        let mut indexes = std::mem::take(&mut table.schema.indexes);
+        scan_into_indexes(&stored, &table.schema, &mut indexes)?;
+        table.schema.indexes = indexes;
+        return Ok((table, Some(stored)));
+    }
+
+    // Rows of a file written before the header was introduced stay in memory, the next
+    // checkpoint replaces the file with one in the current format
+    let bytes = fs::read(&file_path).map_err(io_error("read", &file_path))?;
+    let (table, _) = decode_table(&file_path, table.schema, &bytes)?;
+    Ok((table, None))
+}
+
+/// Opens rows of a table stored in pages from `first_page`, `None` for a legacy file
+fn open_stored_rows(pager: Rc<Pager>, layout: Layout, first_page: u32, schema: &TableSchema) -> Result<Option<StoredRows>, Error> {
+    let header = stored::read_start(&pager, layout, first_page, TABLE_HEADER_LEN)?;
+    let (offset, row_count, legacy) = decode_table_header(pager.path(), schema, &header)?;
+    if legacy {
+        return Ok(None);
+    }
+    let rows = row_count.unwrap_or_default() as usize;
+    Ok(Some(StoredRows::new(pager, layout, first_page, schema_hash_of(schema), offset, rows)))
+}
+
+/// Opens rows of a table written with the header by a checkpoint or an export
+fn open_table_pages(pager: Rc<Pager>, layout: Layout, first_page: u32, schema: &TableSchema) -> Result<StoredRows, Error> {
+    let path = pager.path().to_string();
+    open_stored_rows(pager, layout, first_page, schema)?
+        .ok_or_else(|| Error::CorruptedTable {path, detail: format!("table `{}` has no header", schema.name)})
+}
+
+/// Decodes the content of a table file. Also returns whether it's a legacy file without the header.
+fn decode_table(file_path: &str, schema: TableSchema, bytes: &[u8]) -> Result<(Table, bool), Error> {
+    let mut table = Table {
+        schema,
+        rows: vec![],
+    };
+    let corrupted = |detail: String| Error::CorruptedTable {path: file_path.to_string(), detail};
+
+    let (mut offset, row_count, legacy) = decode_table_header(file_path, &table.schema, bytes)?;
+
+    // Table without columns can't have any data
+    if table.schema.cols.is_empty() && offset < bytes.len() {
+        return Err(corrupted("data in a table without columns".to_string()));
+    }
+
+    if legacy {
+        table.rows = decode_legacy_rows(&table.schema, bytes).map_err(corrupted)?;
+    } else {
+        while offset < bytes.len() {
+            match decode_row(&table.schema, bytes, &mut offset) {
+                Some(row) => table.rows.push(row),
+                None => return Err(corrupted(format!("invalid row {} ending at offset {offset}", table.rows.len()))),
+            }
+        }
+    }
+    if let Some(row_count) = row_count {
+        if row_count != table.rows.len() as u64 {
+            return Err(corrupted(format!("header has {row_count} rows, but file has {}", table.rows.len())));
+        }
+    }
+    rebuild_indexes(&mut table);
+    Ok((table, legacy))
+}
+
+/// Checks the header of a table file and returns the offset and the number of rows
+fn decode_table_header(file_path: &str, schema: &TableSchema, bytes: &[u8]) -> Result<(usize, Option<u64>, bool), Error> {
+    let corrupted = |detail: &str| Error::CorruptedTable {path: file_path.to_string(), detail: detail.to_string()};
+    let header = match bytes.strip_prefix(&TABLE_MAGIC) {
+        Some(header) => header,
+        None => return Ok((0, None, true)),
+    };
+    if header.len() < TABLE_HEADER_LEN - TABLE_MAGIC.len() {
+        return Err(corrupted("truncated header"));
+    }
+    let version = u32::from_le_bytes(header[0..4].try_into().unwrap());
+    if version != TABLE_FORMAT_VERSION {
+        return Err(Error::UnsupportedVersion {path: file_path.to_string(), version});
+    }
+    let schema_hash = u32::from_le_bytes(header[4..8].try_into().unwrap());
+    if schema_hash != schema_hash_of(schema) {
+        return Err(corrupted("table file doesn't match the schema file"));
+    }
+    let row_count = u64::from_le_bytes(header[8..16].try_into().unwrap());
+    Ok((TABLE_HEADER_LEN, Some(row_count), false))
+}
+
+/// Adds stored rows of a table to the indexes
+fn scan_into_indexes(stored: &StoredRows, schema: &TableSchema, indexes: &mut [Index]) -> Result<(), Error> {
+    stored.scan(schema, None, |position, row| {
+        for index in indexes.iter_mut() {
+            index.insert(position, &row[index.col]);
+        }
+        true
+    })
+}
+
+/// Number of rows of the table, in memory or in pages
+fn row_count(database: &Database, table_idx: usize) -> usize {
+    let table = &database.tables[table_idx];
+    match database.paged.tables.get(&table.schema.name) {
+        Some(stored) => stored.len(),
+        None => table.rows.len(),
+    }
+}
+
+/// Size of the table file of the table on disk, changes get into it at the next checkpoint
+fn table_file_size(database: &Database, table_idx: usize) -> Result<u64, Error> {
+    let name = &database.tables[table_idx].schema.name;
+    match database.paged.tables.get(name) {
+        Some(stored) => match stored.layout() {
+            Layout::Plain => Ok(stored.pager().len()),
+            Layout::Chain => single_file::chain_len(stored.pager(), stored.first_page()),
+        },
+        // Tables created since the last checkpoint have no file yet
+        None if database.format == Format::Directory && !database.path.is_empty() => {
+            let path = format!("{}/{}.tbl", database.path, name);
+            match fs::metadata(&path) {
+                Ok(metadata) => Ok(metadata.len()),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(0),
+                Err(err) => Err(io_error("read", &path)(err)),
+            }
+        },
+        None => Ok(0),
+    }
+}
+
+/// Passes rows of the table with their positions to `f` until it returns false
+fn scan_rows(database: &Database, table_idx: usize, positions: Option<&[usize]>, mut f: impl FnMut(usize, &Row) -> bool) -> Result<(), Error> {
+    let table = &database.tables[table_idx];
+    if let Some(stored) = database.paged.tables.get(&table.schema.name) {
+        return stored.scan(&table.schema, positions, f);
+    }
+    match positions {
+        Some(positions) => {
+            for position in positions {
+                if !f(*position, &table.rows[*position]) {
+                    break;
+                }
+            }
+        },
+        None => {
+            for (position, row) in table.rows.iter().enumerate() {
+                if !f(position, row) {
+                    break;
+                }
+            }
+        },
+    }
+    Ok(())
+}
+
+/// Rows of the table at sorted `positions`
+fn rows_at(database: &Database, table_idx: usize, positions: &[usize]) -> Result<Vec<Row>, Error> {
+    let mut rows = vec![];
+    scan_rows(database, table_idx, Some(positions), |_, row| {
+        rows.push(row.clone());
+        true
+    })?;
+    Ok(rows)
+}
+
+/// Loads pages of a stored table with rows at sorted `positions` before the rows are changed
+fn load_positions(database: &mut Database, table_idx: usize, positions: &[usize]) -> Result<(), Error> {
+    let table = &database.tables[table_idx];
+    match database.paged.tables.get_mut(&table.schema.name) {
+        Some(stored) => stored.load(&table.schema, positions),
+        None => Ok(()),
+    }
+}
+
+/// Moves all rows of a stored table into memory before a change of every row
+fn load_rows(database: &mut Database, table_idx: usize) -> Result<(), Error> {
+    let table = &mut database.tables[table_idx];
+    if let Some(stored) = database.paged.tables.get(&table.schema.name) {
+        let mut rows = vec![];
+        stored.scan(&table.schema, None, |_, row| {
+            rows.push(row.clone());
+            true
+        })?;
+        table.rows = rows;
+        database.paged.tables.remove(&table.schema.name);
+    }
+    Ok(())
+}
+
+/// Rows of a table being changed, the changed ones are in memory
+enum RowsMut<'a> {
+    Memory(&'a mut Vec<Row>),
+    Stored(&'a mut StoredRows),
+}
+
+impl RowsMut<'_> {
+    const NOT_LOADED: &'static str = "changed rows of stored tables are loaded";
+
+    fn len(&self) -> usize {
+        match self {
+            RowsMut::Memory(rows) => rows.len(),
+            RowsMut::Stored(stored) => stored.len(),
+        }
+    }
+
+    fn get_mut(&mut self, position: usize) -> &mut Row {
+        match self {
+            RowsMut::Memory(rows) => &mut rows[position],
+            RowsMut::Stored(stored) => stored.get_mut(position).expect(Self::NOT_LOADED),
+        }
+    }
+
+    fn push(&mut self, row: Row) {
+        match self {
+            RowsMut::Memory(rows) => rows.push(row),
+            RowsMut::Stored(stored) => stored.push(row),
+        }
+    }
+
+    fn pop(&mut self) -> Row {
+        match self {
+            RowsMut::Memory(rows) => rows.pop(),
+            RowsMut::Stored(stored) => stored.pop(),
+        }.expect(Self::NOT_LOADED)
+    }
+
+    fn remove(&mut self, position: usize) -> Row {
+        match self {
+            RowsMut::Memory(rows) => rows.remove(position),
+            RowsMut::Stored(stored) => stored.remove(position).expect(Self::NOT_LOADED),
+        }
+    }
+
+    fn insert(&mut self, position: usize, row: Row) {
+        match self {
+            RowsMut::Memory(rows) => rows.insert(position, row),
+            RowsMut::Stored(stored) => stored.insert(position, row).expect(Self::NOT_LOADED),
+        }
+    }
+}
+
+/// Schema and rows of the table for a change of its rows
+fn table_rows_mut(database: &mut Database, table_idx: usize) -> (&mut TableSchema, RowsMut<'_>) {
+    let table = &mut database.tables[table_idx];
+    let rows = match database.paged.tables.get_mut(&table.schema.name) {
+        Some(stored) => RowsMut::Stored(stored),
+        None => RowsMut::Memory(&mut table.rows),
+    };
+    (&mut table.schema, rows)
+}
+
+/// Table with all its rows, rows of a stored table are read without keeping them in the database
+fn read_table(database: &Database, table_idx: usize) -> Result<Cow<'_, Table>, Error> {
+    let table = &database.tables[table_idx];
+    if !database.paged.tables.contains_key(&table.schema.name) {
+        return Ok(Cow::Borrowed(table));
+    }
+    let mut rows = vec![];
+    scan_rows(database, table_idx, None, |_, row| {
+        rows.push(row.clone());
+        true
+    })?;
+    Ok(Cow::Owned(Table {schema: table.schema.clone(), rows}))
+}
+
+/// Writes the content of the table file of the table piece by piece to `out`
+fn write_table(database: &Database, table_idx: usize, out: &mut dyn FnMut(&[u8]) -> Result<(), Error>) -> Result<(), Error> {
+    let table = &database.tables[table_idx];
+    match database.paged.tables.get(&table.schema.name) {
+        Some(stored) => stored.write(&table.schema, out),
+        None => out(&encode_table(table)?),
+    }
+}
+
+fn take_bytes<'a>(bytes: &'a [u8], offset: &mut usize, len: usize) -> Option<&'a [u8]> {
+    let taken = bytes.get(*offset..*offset + len)?;
+    *offset += len;
+    Some(taken)
+}
+
+/// Decodes the row starting at `offset` and moves `offset` past it
+fn decode_row(schema: &TableSchema, bytes: &[u8], offset: &mut usize) -> Option<Row> {
+    let bitmap = take_bytes(bytes, offset, null_bitmap_len(schema))?;
+    let mut row: Row = vec![];
+    let mut nullable_idx = 0;
+    for Col {data_type, nullable, ..} in &schema.cols {
+        let is_null = *nullable && bitmap[nullable_idx / 8] & (1 << (nullable_idx % 8)) != 0;
+        if *nullable {
+            nullable_idx += 1;
+        }
+        let value = match data_type {
+            DataType::Int => {
+                WordType::Int(i32::from_le_bytes(take_bytes(bytes, offset, 4)?.try_into().unwrap()))
+            },
+            DataType::Str => {
+                let len = u32::from_le_bytes(take_bytes(bytes, offset, 4)?.try_into().unwrap());
+                WordType::Str(String::from_utf8(take_bytes(bytes, offset, len as usize)?.to_vec()).ok()?)
+            },
+            DataType::Bool => match take_bytes(bytes, offset, 1)?[0] {
+                0 => WordType::Bool(false),
+                1 => WordType::Bool(true),
+                _ => return None,
+            },
+            DataType::Float => WordType::Float(Float(f64::from_le_bytes(take_bytes(bytes, offset, 8)?.try_into().unwrap()))),
+            DataType::BigInt => WordType::BigInt(i64::from_le_bytes(take_bytes(bytes, offset, 8)?.try_into().unwrap())),
+            DataType::Timestamp => WordType::Timestamp(i64::from_le_bytes(take_bytes(bytes, offset, 8)?.try_into().unwrap())),
+            DataType::Bytes => {
+                let len = u32::from_le_bytes(take_bytes(bytes, offset, 4)?.try_into().unwrap());
+                WordType::Bytes(take_bytes(bytes, offset, len as usize)?.to_vec())
+            },
+            // Columns of types can't be created, such a table is damaged
+            DataType::Type => return None,
+            DataType::Null | DataType::Count => unreachable!(),
+        };
+        // Value of NULL is stored as zeros or an empty string
+        row.push(if is_null { WordType::Null } else { value });
+    }
+    Some(row)
+}
+
+/// Decodes rows of a legacy file without the header, which had only `Int` and `Str` columns
+fn decode_legacy_rows(schema: &TableSchema, bytes: &[u8]) -> Result<Vec<Row>, String> {
+    let mut row_len = 0;
+    for col in &schema.cols {
+        row_len += match (col.data_type, col.nullable) {
+            (DataType::Int, false) => 4,
+            (DataType::Str, false) => LEGACY_STR_LEN,
+            _ => return Err(format!("legacy file can't have column `{}` of type {}", col.name, col_type_to_string(col))),
+        };
+    }
+    if bytes.is_empty() {
+        return Ok(vec![]);
+    }
+    if !bytes.len().is_multiple_of(row_len) {
+        return Err(format!("legacy file size {} isn't a multiple of the row size {row_len}", bytes.len()));
+    }
+
+    let mut rows = vec![];
+    for row_bytes in bytes.chunks(row_len) {
+        let mut row: Row = vec![];
+        let mut offset = 0;
+        for col in &schema.cols {
+            if col.data_type == DataType::Int {
+                row.push(WordType::Int(i32::from_ne_bytes(row_bytes[offset..offset + 4].try_into().unwrap())));
+                offset += 4;
+            } else {
+                let slot = &row_bytes[offset..offset + LEGACY_STR_LEN];
+                let len = slot.iter().position(|&byte| byte == 0).unwrap_or(LEGACY_STR_LEN);
+                row.push(WordType::Str(String::from_utf8_lossy(&slot[..len]).to_string()));
+                offset += LEGACY_STR_LEN;
+            }
+        }
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+/// Writes `content` next to `file_path` into a temporary file for `replace_file()`
+fn write_temp_file(file_path: &str, content: &[u8]) -> Result<String, Error> {
+    stream_temp_file(file_path, |out| out(content))
+}
+
+/// Like `write_temp_file()`, but the content is passed piece by piece by `write` to its argument
+fn stream_temp_file(file_path: &str, write: impl FnOnce(&mut dyn FnMut(&[u8]) -> Result<(), Error>) -> Result<(), Error>) -> Result<String, Error> {
+    let temp_path = format!("{file_path}{TEMP_FILE_SUFFIX}");
+    let file = File::create(&temp_path).map_err(io_error("create", &temp_path))?;
+    let mut file = BufWriter::new(file);
+    write(&mut |bytes| file.write_all(bytes).map_err(io_error("write to", &temp_path)))?;
+    let file = file.into_inner().map_err(|err| io_error("write to", &temp_path)(err.into_error()))?;
+    file.sync_all().map_err(io_error("sync", &temp_path))?;
+    Ok(temp_path)
+}
+
+/// Atomically moves the temporary file over `file_path` and makes the rename durable.
+fn replace_file(temp_path: &str, file_path: &str) -> Result<(), Error> {
+    fs::rename(temp_path, file_path).map_err(io_error("replace", file_path))?;
+    sync_dir(file_path)
+}
+
+fn sync_dir(file_path: &str) -> Result<(), Error> {
+    // Directories can't be opened as files on Windows, the rename is already durable there
+    if cfg!(unix) {
+        let dir = match Path::new(file_path).parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        File::open(dir).and_then(|dir| dir.sync_all()).map_err(io_error("sync", &dir.display().to_string()))?;
+    }
+    Ok(())
+}
+
+fn write_file_atomically(file_path: &str, content: &[u8]) -> Result<(), Error> {
+    let temp_path = write_temp_file(file_path, content)?;
+    replace_file(&temp_path, file_path)
+}
+
+/// Every row starts with a bitmap of NULL values, one bit for every nullable column
+fn null_bitmap_len(schema: &TableSchema) -> usize {
+    schema.cols.iter().filter(|col| col.nullable).count().div_ceil(8)
+}
+
+/// Hash of the columns part of the schema file, indexes don't change the table file
+fn schema_hash_of(schema: &TableSchema) -> u32 {
+    wal::crc32(encode_schema_cols(schema).as_bytes())
+}
+
+/// Header of the file is `<magic><format version: u32><crc32 of the schema file: u32><row count: u64>`
+fn encode_table_header(schema: &TableSchema, row_count: usize) -> Vec<u8> {
+    let mut bytes = TABLE_MAGIC.to_vec();
+    bytes.extend_from_slice(&TABLE_FORMAT_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&schema_hash_of(schema).to_le_bytes());
+    bytes.extend_from_slice(&(row_count as u64).to_le_bytes());
+    bytes
+}
+
+/// Appends the encoded row to `bytes`
+fn encode_row(schema: &TableSchema, row: &Row, bytes: &mut Vec<u8>) -> Result<(), Error> {
+    let mut bitmap = vec![0u8; null_bitmap_len(schema)];
+    let nullable = schema.cols.iter().zip(row).filter(|(col, _)| col.nullable);
+    for (i, (_, word)) in nullable.enumerate() {
+        if *word == WordType::Null {
+            bitmap[i / 8] |= 1 << (i % 8);
+        }
+    }
+    bytes.append(&mut bitmap);
+
+    for (col, word) in schema.cols.iter().zip(row) {
+        match word {
+            WordType::Null => match col.data_type {
+                DataType::Int => bytes.extend_from_slice(&[0; 4]),
+                DataType::Bool => bytes.push(0),
+                DataType::Float | DataType::BigInt | DataType::Timestamp => bytes.extend_from_slice(&[0; 8]),
+                DataType::Str | DataType::Bytes => bytes.extend_from_slice(&0u32.to_le_bytes()),
+                _ => unreachable!(),
+            },
+            WordType::Int(value) => bytes.extend_from_slice(&value.to_le_bytes()),
+            WordType::Str(value) => {
+                bytes.extend_from_slice(&(value.len() as u32).to_le_bytes());
+                bytes.extend_from_slice(value.as_bytes());
+            },
+            WordType::Bool(value) => bytes.push(*value as u8),
+            WordType::Float(Float(value)) => bytes.extend_from_slice(&value.to_le_bytes()),
+            WordType::BigInt(value) | WordType::Timestamp(value) => bytes.extend_from_slice(&value.to_le_bytes()),
+            WordType::Bytes(value) => {
+                bytes.extend_from_slice(&(value.len() as u32).to_le_bytes());
+                bytes.extend_from_slice(value);
+            },
+            WordType::Type(..) => return Err(Error::UnsupportedType {table: schema.name.clone(), column: col.name.clone()}),
+        }
+    }
+    Ok(())
+}
+
+fn encode_table(table: &Table) -> Result<Vec<u8>, Error> {
+    let mut bytes = encode_table_header(&table.schema, table.rows.len());
+    for row in &table.rows {
+        encode_row(&table.schema, row, &mut bytes)?;
+    }
+    Ok(bytes)
+}
+
+fn encode_schema(schema: &TableSchema) -> String {
+    let mut content = encode_schema_cols(schema);
+    for index in &schema.indexes {
+        content.push_str(&format!("index {} {}\n", index.name, schema.cols[index.col].name));
+    }
+    if schema.cols.iter().any(|col| col.auto_increment) {
+        content.push_str(&format!("sequence {}\n", schema.sequence));
+    }
+    content
+}
+
+fn encode_schema_cols(schema: &TableSchema) -> String {
+    let mut content = format!("{}\n", schema.name);
+    for col in &schema.cols {
+        content.push_str(&format!("{}:{}\n", col.name, col_type_to_string(col)));
+    }
+    content
+}
+
+/// Loads the database from the directory `path`, `quarantine` leaves out damaged tables
+fn load_database_from(path: &str, quarantine: bool) -> Result<Database, Error> {
+    let format = if Path::new(path).is_file() { Format::SingleFile } else { Format::Directory };
+    let checkpoint_path = format.checkpoint_path(path);
+    if Path::new(&checkpoint_path).exists() {
+        match format {
+            Format::Directory => {
+                let content = fs::read_to_string(&checkpoint_path).map_err(io_error("read", &checkpoint_path))?;
+                let tables = content.lines().map(String::from).collect::<Vec<String>>();
+                finish_checkpoint(path, &tables)?;
+            },
+            Format::SingleFile => finish_file_checkpoint(path)?,
+        }
+    }
+
+    let mut database = Database {
+        path: path.to_string(),
+        format,
+        paged: PagedTables::new(),
+        transaction: None,
+        tables: vec![],
+        quarantined: vec![],
+        warnings: vec![],
+    };
+
+    let tables = match format {
+        Format::Directory => read_directory_tables(path, &database.paged.pool)?,
+        Format::SingleFile => read_file_tables(path, &database.paged.pool)?,
+    };
+    for (name, table) in tables {
+        match table {
+            Ok((table, stored)) => {
+                if let Some(stored) = stored {
+                    database.paged.tables.insert(name, stored);
+                }
+                database.tables.push(table);
+            },
+            Err(error) if quarantine => database.quarantined.push(QuarantinedTable {name, error}),
+            Err(error) => return Err(error),
+        }
+    }
+
+    for record in wal::read(&format.log_path(path), &mut database.warnings)? {
+        // Changes of a quarantined table stay in the log until the table is repaired
+        if database.is_quarantined(record.table()) {
+            continue;
+        }
+        apply_record(&mut database, record)?;
+    }
+
+    Ok(database)
+}
+
+/// Name of a stored table and the result of reading it, the table with its rows kept in pages
+type LoadedTable = (String, Result<(Table, Option<StoredRows>), Error>);
+
+/// Reads tables of the database in the directory `path`
+fn read_directory_tables(path: &str, pool: &PagePool) -> Result<Vec<LoadedTable>, Error> {
+    let paths = fs::read_dir(path).map_err(io_error("open database directory", path))?;
+    let mut tables = vec![];
+    for file_path in paths {
+        let file = format!("{}", file_path.map_err(io_error("read database directory", path))?.path().display());
+
+        // Leftover of a save interrupted before the rename, the original file is still intact
+        if file.ends_with(TEMP_FILE_SUFFIX) {
+            fs::remove_file(&file).map_err(io_error("remove", &file))?;
+            continue;
+        }
+
+        if !file.ends_with(".tbls") {
+            continue; 
+        }
+
+        let name = Path::new(&file).file_stem().unwrap_or_default().to_string_lossy().to_string();
+        tables.push((name, parse_table_schema(&file).and_then(|schema| read_from_file(path, schema, pool))));
+    }
+    Ok(tables)
+}
+
+/// Opens pages of the single-file database `path` and reads its catalog
+fn read_file_tables(path: &str, pool: &PagePool) -> Result<Vec<LoadedTable>, Error> {
+    let temp_path = format!("{path}{TEMP_FILE_SUFFIX}");
+    if Path::new(&temp_path).exists() {
+        fs::remove_file(&temp_path).map_err(io_error("remove", &temp_path))?;
+    }
+
+    let pager = Rc::new(Pager::open(path, pool)?);
+    let mut tables = vec![];
+    for single_file::Entry {schema, first_page} in single_file::read_catalog(&pager)? {
+        let name = schema.lines().next().unwrap_or_default().trim().to_string();
+        let table = parse_schema(&schema, path).and_then(|mut schema| {
+            let stored = open_table_pages(pager.clone(), Layout::Chain, first_page, &schema)?;
+            let mut indexes = std::mem::take(&mut schema.indexes);
+            scan_into_indexes(&stored, &schema, &mut indexes)?;
+            schema.indexes = indexes;
+            Ok((Table {schema, rows: vec![]}, Some(stored)))
+        });
+        tables.push((name, table));
+    }
+    Ok(tables)
+}
+
+/// Opens the table files written by a checkpoint, rows keep their positions
+fn reopen_tables(database: &mut Database) -> Result<(), Error> {
+    let pool = &database.paged.pool;
+    let mut tables = HashMap::new();
+    match database.format {
+        Format::Directory => {
+            for table in &database.tables {
+                let pager = Rc::new(Pager::open(&format!("{}/{}.tbl", database.path, table.schema.name), pool)?);
+                tables.insert(table.schema.name.clone(), open_table_pages(pager, Layout::Plain, 0, &table.schema)?);
+            }
+        },
+        Format::SingleFile => {
+            let pager = Rc::new(Pager::open(&database.path, pool)?);
+            for (table, entry) in database.tables.iter().zip(single_file::read_catalog(&pager)?) {
+                tables.insert(table.schema.name.clone(), open_table_pages(pager.clone(), Layout::Chain, entry.first_page, &table.schema)?);
+            }
+        },
+    }
+    for table in &mut database.tables {
+        table.rows = vec![];
+    }
+    database.paged.tables = tables;
+    Ok(())
+}
+
+/// Writes new files of the changed tables and the checkpoint file, returns names of the tables
+fn prepare_checkpoint(path: &str, database: &Database) -> Result<Vec<String>, Error> {
+    let mut tables = vec![];
+    for (table_idx, table) in database.tables.iter().enumerate() {
+        let file_path = format!("{}/{}.tbl", path, table.schema.name);
+        let unchanged = database.paged.tables.get(&table.schema.name)
+            .is_some_and(|stored| stored.is_unchanged(schema_hash_of(&table.schema)) && stored.pager().path() == file_path);
+        if !unchanged {
+            stream_temp_file(&file_path, |out| write_table(database, table_idx, out))?;
+        }
+        write_temp_file(&format!("{}/{}.tbls", path, table.schema.name), encode_schema(&table.schema).as_bytes())?;
+        tables.push(table.schema.name.clone());
+    }
+
+    let mut content = String::new();
+    for name in &tables {
+        content.push_str(&format!("{name}\n"));
+    }
+    write_file_atomically(&Format::Directory.checkpoint_path(path), content.as_bytes())?;
+    Ok(tables)
+}
+
+/// Moves the new table files in place, removes files of dropped tables and empties the log
+fn finish_checkpoint(path: &str, tables: &[String]) -> Result<(), Error> {
+    let paths = fs::read_dir(path).map_err(io_error("open database directory", path))?;
+
+    for file_path in paths {
+        let file = format!("{}", file_path.map_err(io_error("read database directory", path))?.path().display());
+
+        if let Some(target) = file.strip_suffix(TEMP_FILE_SUFFIX) {
+            replace_file(&file, target)?;
+            continue;
+        }
+
+        let table_name = match file.strip_suffix(".tbls").or(file.strip_suffix(".tbl")) {
+            Some(stem) => Path::new(stem).file_name().unwrap_or_default().to_string_lossy().to_string(),
+            None => continue,
+        };
+        if !tables.contains(&table_name) {
+            fs::remove_file(&file).map_err(io_error("remove", &file))?;
+        }
+    }
+
+    write_file_atomically(&wal::log_path(path), &[])?;
+
+    let checkpoint_path = Format::Directory.checkpoint_path(path);
+    fs::remove_file(&checkpoint_path).map_err(io_error("remove", &checkpoint_path))?;
+    sync_dir(&checkpoint_path)
+}
+
+fn save_database_to(path: &str, database: &Database) -> Result<(), Error> {
+    let tables = prepare_checkpoint(path, database)?;
+    finish_checkpoint(path, &tables)
+}
+
+/// Writes the new database file next to the old one and creates the checkpoint file
+fn prepare_file_checkpoint(path: &str, database: &Database) -> Result<(), Error> {
+    let mut writer = single_file::Writer::create(&format!("{path}{TEMP_FILE_SUFFIX}"))?;
+    let mut entries = vec![];
+    for (table_idx, table) in database.tables.iter().enumerate() {
+        // Pages of tables which weren't changed are copied without decoding their rows
+        let first_page = match database.paged.tables.get(&table.schema.name) {
+            Some(stored) if stored.layout() == Layout::Chain && stored.is_unchanged(schema_hash_of(&table.schema)) => {
+                writer.copy_chain(stored.pager(), stored.first_page())?
+            },
+            _ => {
+                let mut chain = writer.chain();
+                write_table(database, table_idx, &mut |bytes| chain.write(bytes))?;
+                chain.finish()?
+            },
+        };
+        entries.push(single_file::Entry {schema: encode_schema(&table.schema), first_page});
+    }
+    writer.finish(&entries)?;
+    write_file_atomically(&Format::SingleFile.checkpoint_path(path), &[])
+}
+
+fn finish_file_checkpoint(path: &str) -> Result<(), Error> {
+    let temp_path = format!("{path}{TEMP_FILE_SUFFIX}");
+    if Path::new(&temp_path).exists() {
+        replace_file(&temp_path, path)?;
+    }
+    write_file_atomically(&Format::SingleFile.log_path(path), &[])?;
+
+    let checkpoint_path = Format::SingleFile.checkpoint_path(path);
+    fs::remove_file(&checkpoint_path).map_err(io_error("remove", &checkpoint_path))?;
+    sync_dir(&checkpoint_path)
+}
+
+fn save_file_to(path: &str, database: &Database) -> Result<(), Error> {
+    prepare_file_checkpoint(path, database)?;
+    finish_file_checkpoint(path)
+}
+
+/// Folds the write-ahead log into the table files of the database
+fn checkpoint_database(database: &mut Database) -> Result<(), Error> {
+    if database.path.is_empty() || database.transaction.is_some() || !database.quarantined.is_empty() {
+        return Ok(());
+    }
+    match database.format {
+        Format::Directory => save_database_to(&database.path, database)?,
+        Format::SingleFile => save_file_to(&database.path, database)?,
+    }
+    reopen_tables(database)
+}
+
+/// Connection to a database stored in a directory or in a single file
+#[derive(Debug)]
+pub struct Connection {
+    database: Database,
+}
+
+impl Connection {
+    /// Opens the database in the directory or the single file `path` and recovers changes
+    /// from the write-ahead log.
+    pub fn open(path: &str) -> Result<Self, Error> {
+        Ok(Self {
+            database: load_database_from(path, false)?,
+        })
+    }
+
+    /// Opens the database like `open()`, but a table with damaged files is quarantined instead
+    /// of failing. Quarantined tables can't be used by queries, their files and logged changes
+    /// are kept untouched until the database is opened again after a repair.
+    pub fn open_with_quarantine(path: &str) -> Result<Self, Error> {
+        Ok(Self {
+            database: load_database_from(path, true)?,
+        })
+    }
+
+    /// Creates a database which lives only in memory.
+    pub fn open_in_memory() -> Self {
+        Self {
+            database: Database {
+                path: String::new(),
+                format: Format::Directory,
+                paged: PagedTables::new(),
+                transaction: None,
+                tables: vec![],
+                quarantined: vec![],
+                warnings: vec![],
+            },
+        }
+    }
+
+    /// Executes the query and returns the result of the last `select` in it, if any.
+    pub fn execute(&mut self, query: &str) -> Result<Option<Table>, Error> {
+        let table = execute_query(query, &mut self.database)?;
+        if wal::size(&self.database.format.log_path(&self.database.path)) > CHECKPOINT_LOG_SIZE {
+            checkpoint_database(&mut self.database)?;
+        }
+        Ok(table)
+    }
+
+    pub fn format(&self) -> Format {
+        self.database.format
+    }
+
+    /// Writes a copy of the database to `path` in the `format`, replacing a database already stored there.
+    /// Exported copy of a directory is a single file and vice versa, so it also converts between the formats.
+    pub fn export(&self, path: &str, format: Format) -> Result<(), Error> {
+        if self.database.transaction.is_some() {
+            return Err(Error::ExportUnavailable {reason: "a transaction is running"});
+        }
+        if !self.database.quarantined.is_empty() {
+            return Err(Error::ExportUnavailable {reason: "some tables are quarantined"});
+        }
+        match format {
+            Format::Directory => {
+                fs::create_dir_all(path).map_err(io_error("create directory", path))?;
+                save_database_to(path, &self.database)
+            },
+            Format::SingleFile => save_file_to(path, &self.database),
+        }
+    }
+
+    pub fn in_transaction(&self) -> bool {
+        self.database.transaction.is_some()
+    }
+
+    /// Tables which failed to load when the database was opened with `open_with_quarantine()`.
+    pub fn quarantined_tables(&self) -> &[QuarantinedTable] {
+        &self.database.quarantined
+    }
+
+    /// Warnings of opening the database and of executed queries since the last call.
+    pub fn take_warnings(&mut self) -> Vec<Warning> {
+        std::mem::take(&mut self.database.warnings)
+    }
+
+    /// Rolls back an unfinished transaction and folds the write-ahead log into the table files.
+    pub fn close(mut self) -> Result<(), Error> {
+        if let Some(transaction) = self.database.transaction.take() {
+            rollback_transaction(&mut self.database, transaction);
+        }
+        checkpoint_database(&mut self.database)
+    }
+}
@@ -0,0 +1,190 @@
+//! Reading pages of database files through a bounded buffer pool shared by all open files
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::rc::Rc;
+
+use crate::Error;
+use crate::error::io_error;
+use crate::single_file::{PAGE_HEADER_LEN, PAGE_SIZE};
+
+/// Number of pages kept in memory, 1 MiB
+pub const POOL_PAGES: usize = 256;
+
+#[derive(Debug)]
+struct Frame {
+    data: Rc<[u8]>,
+    last_used: u64,
+}
+
+/// Recently read pages of all files. When it's full, the least recently used page is evicted.
+#[derive(Debug)]
+struct BufferPool {
+    capacity: usize,
+    /// Pages by the id of their pager and their number
+    frames: HashMap<(u32, u32), Frame>,
+    clock: u64,
+    reads: u64,
+    next_id: u32,
+}
+
+/// Buffer pool shared by pagers of the files of one database
+#[derive(Debug, Clone)]
+pub struct PagePool(Rc<RefCell<BufferPool>>);
+
+impl PagePool {
+    pub fn new(pages: usize) -> Self {
+        Self(Rc::new(RefCell::new(BufferPool {
+            capacity: pages.max(1),
+            frames: HashMap::new(),
+            clock: 0,
+            reads: 0,
+            next_id: 0,
+        })))
+    }
+
+    /// Number of pages read from the files, pages found in the pool aren't counted
+    #[cfg(test)]
+    pub fn reads(&self) -> u64 {
+        self.0.borrow().reads
+    }
+
+    #[cfg(test)]
+    pub fn cached_pages(&self) -> usize {
+        self.0.borrow().frames.len()
+    }
+}
+
+/// How content is laid out in pages of a file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Layout {
+    /// Pages of a single-file database start with a header and are linked into chains
+    Chain,
+    /// Pages of a table file are its consecutive parts, the last one may be shorter
+    Plain,
+}
+
+/// Content of a page without its header and the page which continues it
+pub struct PageContent {
+    data: Rc<[u8]>,
+    start: usize,
+    end: usize,
+    next: Option<u32>,
+}
+
+impl PageContent {
+    pub fn bytes(&self) -> &[u8] {
+        &self.data[self.start..self.end]
+    }
+
+    pub fn next(&self) -> Option<u32> {
+        self.next
+    }
+}
+
+#[derive(Debug)]
+pub struct Pager {
+    path: String,
+    file: File,
+    len: u64,
+    id: u32,
+    pool: PagePool,
+}
+
+impl Pager {
+    pub fn open(path: &str, pool: &PagePool) -> Result<Self, Error> {
+        let file = File::open(path).map_err(io_error("open", path))?;
+        let len = file.metadata().map_err(io_error("stat", path))?.len();
+        let mut buffer_pool = pool.0.borrow_mut();
+        let id = buffer_pool.next_id;
+        buffer_pool.next_id += 1;
+        Ok(Self {path: path.to_string(), file, len, id, pool: pool.clone()})
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Size of the file in bytes
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn page_count(&self) -> u32 {
+        self.len.div_ceil(PAGE_SIZE as u64) as u32
+    }
+
+    pub fn page(&self, page: u32) -> Result<Rc<[u8]>, Error> {
+        if page >= self.page_count() {
+            return Err(Error::CorruptedFile {path: self.path.clone(), detail: format!("page {page} is out of the file")});
+        }
+        let mut pool = self.pool.0.borrow_mut();
+        pool.clock += 1;
+        let clock = pool.clock;
+        if let Some(frame) = pool.frames.get_mut(&(self.id, page)) {
+            frame.last_used = clock;
+            return Ok(frame.data.clone());
+        }
+
+        let start = page as u64 * PAGE_SIZE as u64;
+        let mut data = vec![0; (self.len - start).min(PAGE_SIZE as u64) as usize];
+        let mut file = &self.file;
+        file.seek(SeekFrom::Start(start)).map_err(io_error("seek in", &self.path))?;
+        file.read_exact(&mut data).map_err(io_error("read", &self.path))?;
+        let data: Rc<[u8]> = data.into();
+        pool.reads += 1;
+
+        if pool.frames.len() >= pool.capacity {
+            let evicted = pool.frames.iter().min_by_key(|(_, frame)| frame.last_used).map(|(page, _)| *page);
+            if let Some(evicted) = evicted {
+                pool.frames.remove(&evicted);
+            }
+        }
+        pool.frames.insert((self.id, page), Frame {data: data.clone(), last_used: clock});
+        Ok(data)
+    }
+
+    /// First `len` bytes of the page, read from the pool or the file without adding the page to the pool
+    pub fn page_start(&self, page: u32, len: usize) -> Result<Vec<u8>, Error> {
+        if page >= self.page_count() {
+            return Err(Error::CorruptedFile {path: self.path.clone(), detail: format!("page {page} is out of the file")});
+        }
+        if let Some(frame) = self.pool.0.borrow().frames.get(&(self.id, page)) {
+            return Ok(frame.data[..len.min(frame.data.len())].to_vec());
+        }
+        let start = page as u64 * PAGE_SIZE as u64;
+        let mut data = vec![0; (self.len - start).min(len as u64) as usize];
+        let mut file = &self.file;
+        file.seek(SeekFrom::Start(start)).map_err(io_error("seek in", &self.path))?;
+        file.read_exact(&mut data).map_err(io_error("read", &self.path))?;
+        Ok(data)
+    }
+
+    /// Content of the page in the `layout`
+    pub fn content(&self, page: u32, layout: Layout) -> Result<PageContent, Error> {
+        let data = self.page(page)?;
+        match layout {
+            Layout::Chain => {
+                let next = u32::from_le_bytes(data[0..4].try_into().unwrap());
+                let used = u32::from_le_bytes(data[4..8].try_into().unwrap()) as usize;
+                if data.len() < PAGE_SIZE || used > PAGE_SIZE - PAGE_HEADER_LEN {
+                    return Err(Error::CorruptedFile {path: self.path.clone(), detail: "invalid page header".to_string()});
+                }
+                Ok(PageContent {data, start: PAGE_HEADER_LEN, end: PAGE_HEADER_LEN + used, next: Some(next).filter(|next| *next != 0)})
+            },
+            Layout::Plain => {
+                let end = data.len();
+                Ok(PageContent {data, start: 0, end, next: Some(page + 1).filter(|next| *next < self.page_count())})
+            },
+        }
+    }
+}
+
+/// Pages of a closed file are dropped from the pool
+impl Drop for Pager {
+    fn drop(&mut self) {
+        self.pool.0.borrow_mut().frames.retain(|(id, _), _| *id != self.id);
+    }
+}
@@ -0,0 +1,226 @@
+//! Single-file database: schemas and table files of all tables stored in pages of one file.
+//!
+//! Page 0 is the header `<magic><format version: u32><page size: u32><page count: u32><catalog page: u32>`.
+//! Other pages are linked into chains, every page starts with `<next page: u32><used bytes: u32>`
+//! and next page 0 ends the chain. The catalog chain holds `<table count: u32>` followed by
+//! `<schema length: u32><schema file><first data page: u32>` for every table, the data chain
+//! of a table holds the content of its table file.
+
+use std::fs::File;
+use std::io::{Seek, SeekFrom, Write};
+
+use crate::Error;
+use crate::error::io_error;
+use crate::pager::{Layout, PageContent, Pager};
+
+pub const PAGE_SIZE: usize = 4096;
+const MAGIC: [u8; 8] = *b"\x89RDBFILE";
+const FORMAT_VERSION: u32 = 1;
+pub const PAGE_HEADER_LEN: usize = 8;
+const PAGE_CONTENT_LEN: usize = PAGE_SIZE - PAGE_HEADER_LEN;
+
+/// Stored table, its schema file and the first page of its table file
+pub struct Entry {
+    pub schema: String,
+    pub first_page: u32,
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(offset..offset + 4)?.try_into().unwrap()))
+}
+
+fn corrupted(pager: &Pager, detail: &str) -> Error {
+    Error::CorruptedFile {path: pager.path().to_string(), detail: detail.to_string()}
+}
+
+/// Reads content of a chain page by page
+pub struct Chain<'a> {
+    pager: &'a Pager,
+    next: u32,
+    // A chain visits every page at most once, a longer one has a loop
+    remaining: u32,
+}
+
+impl<'a> Chain<'a> {
+    pub fn new(pager: &'a Pager, first_page: u32) -> Self {
+        Self {pager, next: first_page, remaining: pager.page_count()}
+    }
+
+    /// Content of the next page of the chain, `None` after the last one
+    pub fn next_page(&mut self) -> Result<Option<PageContent>, Error> {
+        if self.next == 0 {
+            return Ok(None);
+        }
+        if self.remaining == 0 {
+            return Err(corrupted(self.pager, "loop in a chain of pages"));
+        }
+        self.remaining -= 1;
+        let page = self.pager.content(self.next, Layout::Chain)?;
+        self.next = page.next().unwrap_or(0);
+        Ok(Some(page))
+    }
+
+    pub fn read_to_end(mut self) -> Result<Vec<u8>, Error> {
+        let mut content = vec![];
+        while let Some(page) = self.next_page()? {
+            content.extend_from_slice(page.bytes());
+        }
+        Ok(content)
+    }
+}
+
+/// Length of the content of a chain, only headers of its pages are read
+pub fn chain_len(pager: &Pager, first_page: u32) -> Result<u64, Error> {
+    let mut len = 0;
+    let mut next = first_page;
+    for _ in 0..pager.page_count() {
+        if next == 0 {
+            return Ok(len);
+        }
+        let header = pager.page_start(next, PAGE_HEADER_LEN)?;
+        let used = u32_at(&header, 4).filter(|used| *used as usize <= PAGE_CONTENT_LEN).ok_or_else(|| corrupted(pager, "invalid page header"))?;
+        len += used as u64;
+        next = u32_at(&header, 0).unwrap();
+    }
+    Err(corrupted(pager, "loop in a chain of pages"))
+}
+
+/// Checks the header of the file and reads its catalog
+pub fn read_catalog(pager: &Pager) -> Result<Vec<Entry>, Error> {
+    if pager.len() < PAGE_SIZE as u64 {
+        return Err(corrupted(pager, "not a database file"));
+    }
+    let header = pager.page(0)?;
+    if header[..MAGIC.len()] != MAGIC {
+        return Err(corrupted(pager, "not a database file"));
+    }
+    let version = u32_at(&header, 8).unwrap();
+    if version != FORMAT_VERSION {
+        return Err(Error::UnsupportedVersion {path: pager.path().to_string(), version});
+    }
+    if u32_at(&header, 12).unwrap() as usize != PAGE_SIZE {
+        return Err(corrupted(pager, "unsupported page size"));
+    }
+    if u32_at(&header, 16).unwrap() != pager.page_count() {
+        return Err(corrupted(pager, "file size doesn't match the page count"));
+    }
+
+    let catalog = Chain::new(pager, u32_at(&header, 20).unwrap()).read_to_end()?;
+    let invalid_catalog = || corrupted(pager, "invalid catalog");
+    let count = u32_at(&catalog, 0).ok_or_else(invalid_catalog)?;
+    let mut offset = 4;
+    let mut entries = vec![];
+    for _ in 0..count {
+        let len = u32_at(&catalog, offset).ok_or_else(invalid_catalog)? as usize;
+        let schema = catalog.get(offset + 4..offset + 4 + len).ok_or_else(invalid_catalog)?;
+        let schema = String::from_utf8(schema.to_vec()).map_err(|_| invalid_catalog())?;
+        offset += 4 + len;
+        let first_page = u32_at(&catalog, offset).ok_or_else(invalid_catalog)?;
+        if first_page == 0 || first_page >= pager.page_count() {
+            return Err(invalid_catalog());
+        }
+        offset += 4;
+        entries.push(Entry {schema, first_page});
+    }
+    Ok(entries)
+}
+
+/// Writes a new database file page by page, the header is written last by `finish()`
+pub struct Writer {
+    path: String,
+    file: File,
+    page_count: u32,
+}
+
+impl Writer {
+    pub fn create(path: &str) -> Result<Self, Error> {
+        let mut file = File::create(path).map_err(io_error("create", path))?;
+        file.write_all(&[0; PAGE_SIZE]).map_err(io_error("write to", path))?;
+        Ok(Self {path: path.to_string(), file, page_count: 1})
+    }
+
+    fn push_page(&mut self, last: bool, content: &[u8]) -> Result<(), Error> {
+        let next = if last { 0 } else { self.page_count + 1 };
+        let mut page = Vec::with_capacity(PAGE_SIZE);
+        page.extend_from_slice(&next.to_le_bytes());
+        page.extend_from_slice(&(content.len() as u32).to_le_bytes());
+        page.extend_from_slice(content);
+        page.resize(PAGE_SIZE, 0);
+        self.file.write_all(&page).map_err(io_error("write to", &self.path))?;
+        self.page_count += 1;
+        Ok(())
+    }
+
+    /// Starts a chain of pages whose content is written piece by piece
+    pub fn chain(&mut self) -> ChainWriter<'_> {
+        let first = self.page_count;
+        ChainWriter {writer: self, first, pending: Vec::with_capacity(PAGE_CONTENT_LEN)}
+    }
+
+    /// Writes the chain of pages holding `content` and returns its first page
+    pub fn push_chain(&mut self, content: &[u8]) -> Result<u32, Error> {
+        let mut chain = self.chain();
+        chain.write(content)?;
+        chain.finish()
+    }
+
+    /// Copies the chain starting at `first_page` of another file without loading all of it
+    pub fn copy_chain(&mut self, pager: &Pager, first_page: u32) -> Result<u32, Error> {
+        let first = self.page_count;
+        let mut chain = Chain::new(pager, first_page);
+        let mut page = chain.next_page()?;
+        while let Some(content) = page {
+            page = chain.next_page()?;
+            self.push_page(page.is_none(), content.bytes())?;
+        }
+        Ok(first)
+    }
+
+    /// Writes the catalog of `entries` and the header and flushes the file to disk
+    pub fn finish(mut self, entries: &[Entry]) -> Result<(), Error> {
+        let mut catalog = (entries.len() as u32).to_le_bytes().to_vec();
+        for Entry {schema, first_page} in entries {
+            catalog.extend_from_slice(&(schema.len() as u32).to_le_bytes());
+            catalog.extend_from_slice(schema.as_bytes());
+            catalog.extend_from_slice(&first_page.to_le_bytes());
+        }
+        let catalog_page = self.push_chain(&catalog)?;
+
+        let mut header = MAGIC.to_vec();
+        header.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        header.extend_from_slice(&(PAGE_SIZE as u32).to_le_bytes());
+        header.extend_from_slice(&self.page_count.to_le_bytes());
+        header.extend_from_slice(&catalog_page.to_le_bytes());
+        self.file.seek(SeekFrom::Start(0)).map_err(io_error("seek in", &self.path))?;
+        self.file.write_all(&header).map_err(io_error("write to", &self.path))?;
+        self.file.sync_all().map_err(io_error("sync", &self.path))
+    }
+}
+
+/// Chain being written, a full page is written once more content follows it
+pub struct ChainWriter<'a> {
+    writer: &'a mut Writer,
+    first: u32,
+    pending: Vec<u8>,
+}
+
+impl ChainWriter<'_> {
+    pub fn write(&mut self, mut content: &[u8]) -> Result<(), Error> {
+        while !content.is_empty() {
+            if self.pending.len() == PAGE_CONTENT_LEN {
+                self.writer.push_page(false, &self.pending)?;
+                self.pending.clear();
+            }
+            let len = content.len().min(PAGE_CONTENT_LEN - self.pending.len());
+            self.pending.extend_from_slice(&content[..len]);
+            content = &content[len..];
+        }
+        Ok(())
+    }
+
+    /// Writes the last page and returns the first page of the chain
+    pub fn finish(self) -> Result<u32, Error> {
+        self.writer.push_page(true, &self.pending)?;
+        Ok(self.first)
+    }
+}
@@ -0,0 +1,393 @@
+//! Rows of tables kept in pages of their table files. Queries read the rows page by page, a change
+//! loads into memory only the pages with the changed rows and new rows are kept in memory until
+//! the next checkpoint writes the table file again.
+
+use std::ops::Range;
+use std::rc::Rc;
+
+use crate::pager::{Layout, Pager};
+use crate::{decode_row, encode_row, encode_table_header, Error, Row, TableSchema};
+
+/// Place in a table file, a page and an offset in the content of the page
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Position {
+    page: u32,
+    offset: usize,
+}
+
+/// Consecutive rows of a table
+#[derive(Debug)]
+enum Run {
+    /// Rows encoded in pages, starting at `start`
+    Stored {start: Position, rows: usize},
+    /// Rows of the pages with changed rows and new rows
+    Loaded(Vec<Row>),
+}
+
+impl Run {
+    fn len(&self) -> usize {
+        match self {
+            Run::Stored {rows, ..} => *rows,
+            Run::Loaded(rows) => rows.len(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct StoredRows {
+    pager: Rc<Pager>,
+    layout: Layout,
+    /// Page with the header of the table file
+    first_page: u32,
+    /// Hash of the schema in the header of the table file
+    schema_hash: u32,
+    runs: Vec<Run>,
+}
+
+impl StoredRows {
+    /// Rows of the table file in pages from `first_page`, `rows` of them start at `offset`
+    pub fn new(pager: Rc<Pager>, layout: Layout, first_page: u32, schema_hash: u32, offset: usize, rows: usize) -> Self {
+        let start = Position {page: first_page, offset};
+        Self {pager, layout, first_page, schema_hash, runs: vec![Run::Stored {start, rows}]}
+    }
+
+    pub fn pager(&self) -> &Pager {
+        &self.pager
+    }
+
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    pub fn first_page(&self) -> u32 {
+        self.first_page
+    }
+
+    pub fn len(&self) -> usize {
+        self.runs.iter().map(Run::len).sum()
+    }
+
+    /// Number of rows in memory
+    #[cfg(test)]
+    pub fn loaded_rows(&self) -> usize {
+        self.runs.iter().filter(|run| matches!(run, Run::Loaded(..))).map(Run::len).sum()
+    }
+
+    /// Whether the table file holds exactly these rows for the schema with the hash
+    pub fn is_unchanged(&self, schema_hash: u32) -> bool {
+        self.schema_hash == schema_hash && matches!(self.runs[..], [Run::Stored {..}])
+    }
+
+    /// Passes rows with their positions to `f` until it returns false. With sorted `positions` only
+    /// rows at them are passed, pages without them and pages after the last one aren't read.
+    pub fn scan(&self, schema: &TableSchema, positions: Option<&[usize]>, mut f: impl FnMut(usize, &Row) -> bool) -> Result<(), Error> {
+        let mut base = 0;
+        for run in &self.runs {
+            let len = run.len();
+            let wanted = positions.map(|positions| &positions[in_range(positions, base..base + len)]);
+            match (run, wanted) {
+                (Run::Loaded(rows), Some(wanted)) => {
+                    for position in wanted {
+                        if !f(*position, &rows[position - base]) {
+                            return Ok(());
+                        }
+                    }
+                },
+                (Run::Loaded(rows), None) => {
+                    for (i, row) in rows.iter().enumerate() {
+                        if !f(base + i, row) {
+                            return Ok(());
+                        }
+                    }
+                },
+                (Run::Stored {start, rows}, wanted) => {
+                    let count = match wanted {
+                        Some(wanted) => wanted.last().map_or(0, |last| last - base + 1),
+                        None => *rows,
+                    };
+                    if count == 0 {
+                        base += len;
+                        continue;
+                    }
+                    let mut reader = RowReader::new(self, schema, *start)?;
+                    for i in 0..count {
+                        let (row, _) = reader.next_row()?;
+                        let visited = wanted.is_none_or(|wanted| wanted.binary_search(&(base + i)).is_ok());
+                        if visited && !f(base + i, &row) {
+                            return Ok(());
+                        }
+                    }
+                },
+            }
+            base += len;
+        }
+        Ok(())
+    }
+
+    /// Loads rows of pages with rows at sorted `positions` into memory, so that they can be changed.
+    /// Rows belong to the page where they start, pages after the last position aren't read.
+    pub fn load(&mut self, schema: &TableSchema, positions: &[usize]) -> Result<(), Error> {
+        let mut bases = vec![];
+        let mut base = 0;
+        for run in &self.runs {
+            bases.push(base);
+            base += run.len();
+        }
+        for i in (0..self.runs.len()).rev() {
+            let range = in_range(positions, bases[i]..bases[i] + self.runs[i].len());
+            if let Run::Stored {start, rows} = self.runs[i] {
+                if !range.is_empty() {
+                    let runs = self.split(schema, start, rows, bases[i], &positions[range])?;
+                    self.runs.splice(i..i + 1, runs);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Splits stored rows into runs of pages with and without rows at `positions`
+    fn split(&self, schema: &TableSchema, start: Position, rows: usize, base: usize, positions: &[usize]) -> Result<Vec<Run>, Error> {
+        let mut runs = vec![];
+        let push = |runs: &mut Vec<Run>, group: Vec<Row>, start: Position, first: usize| {
+            if in_range(positions, base + first..base + first + group.len()).is_empty() {
+                push_stored(runs, start, group.len());
+            } else {
+                runs.push(Run::Loaded(group));
+            }
+        };
+        let last = positions.last().map_or(0, |last| last - base);
+        let mut reader = RowReader::new(self, schema, start)?;
+        let mut group = vec![];
+        let mut group_start = start;
+        let mut group_first = 0;
+        for i in 0..rows {
+            let (row, position) = reader.next_row()?;
+            if i > 0 && position.page != group_start.page {
+                push(&mut runs, std::mem::take(&mut group), group_start, group_first);
+                if i > last {
+                    push_stored(&mut runs, position, rows - i);
+                    return Ok(runs);
+                }
+            }
+            if group.is_empty() {
+                group_start = position;
+                group_first = i;
+            }
+            group.push(row);
+        }
+        if !group.is_empty() {
+            push(&mut runs, group, group_start, group_first);
+        }
+        Ok(runs)
+    }
+
+    /// Loaded run with the row at the position, or with its end for an insert with `end`
+    fn loaded_run(&mut self, position: usize, end: bool) -> Option<(&mut Vec<Row>, usize)> {
+        let mut base = 0;
+        for run in &mut self.runs {
+            let len = run.len();
+            let inside = position < base + len || (end && position == base + len);
+            if let Run::Loaded(rows) = run {
+                if inside && position >= base {
+                    return Some((rows, position - base));
+                }
+            }
+            base += len;
+        }
+        None
+    }
+
+    /// Loaded row at the position
+    pub fn get_mut(&mut self, position: usize) -> Option<&mut Row> {
+        let (rows, i) = self.loaded_run(position, false)?;
+        rows.get_mut(i)
+    }
+
+    /// Removes the loaded row at the position, runs stay even when they become empty
+    pub fn remove(&mut self, position: usize) -> Option<Row> {
+        let (rows, i) = self.loaded_run(position, false)?;
+        Some(rows.remove(i))
+    }
+
+    /// Inserts the row into a loaded run next to the position. Returns the row back when the
+    /// position is between stored rows.
+    pub fn insert(&mut self, position: usize, row: Row) -> Result<(), Row> {
+        match self.loaded_run(position, true) {
+            Some((rows, i)) => {
+                rows.insert(i, row);
+                Ok(())
+            },
+            None => Err(row),
+        }
+    }
+
+    pub fn push(&mut self, row: Row) {
+        match self.runs.last_mut() {
+            Some(Run::Loaded(rows)) => rows.push(row),
+            _ => self.runs.push(Run::Loaded(vec![row])),
+        }
+    }
+
+    /// Removes the last row when it's loaded
+    pub fn pop(&mut self) -> Option<Row> {
+        match self.runs.iter_mut().rev().find(|run| run.len() > 0)? {
+            Run::Loaded(rows) => rows.pop(),
+            Run::Stored {..} => None,
+        }
+    }
+
+    /// Writes the content of the table file, encoded rows of stored runs are copied
+    pub fn write(&self, schema: &TableSchema, out: &mut dyn FnMut(&[u8]) -> Result<(), Error>) -> Result<(), Error> {
+        out(&encode_table_header(schema, self.len()))?;
+        let mut bytes = vec![];
+        for run in &self.runs {
+            match run {
+                Run::Stored {start, rows} => {
+                    let mut reader = RowReader::new(self, schema, *start)?;
+                    for _ in 0..*rows {
+                        reader.next_row()?;
+                        out(reader.last_bytes())?;
+                    }
+                },
+                Run::Loaded(rows) => {
+                    for row in rows {
+                        bytes.clear();
+                        encode_row(schema, row, &mut bytes)?;
+                        out(&bytes)?;
+                    }
+                },
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Range of sorted `positions` which are in the range of rows
+fn in_range(positions: &[usize], rows: Range<usize>) -> Range<usize> {
+    positions.partition_point(|position| *position < rows.start)..positions.partition_point(|position| *position < rows.end)
+}
+
+/// Adds stored rows after the last run, merging them with stored rows right before them
+fn push_stored(runs: &mut Vec<Run>, start: Position, rows: usize) {
+    match runs.last_mut() {
+        Some(Run::Stored {rows: previous, ..}) => *previous += rows,
+        _ => runs.push(Run::Stored {start, rows}),
+    }
+}
+
+/// Bytes at the start of the table file in pages from `first_page`, up to `len` of them
+pub fn read_start(pager: &Pager, layout: Layout, first_page: u32, len: usize) -> Result<Vec<u8>, Error> {
+    let mut bytes = vec![];
+    let mut next = Some(first_page);
+    // Pages of a chain with a loop are visited at most once
+    for _ in 0..pager.page_count() {
+        let page = match next {
+            Some(page) if bytes.len() < len => pager.content(page, layout)?,
+            _ => break,
+        };
+        bytes.extend_from_slice(page.bytes());
+        next = page.next();
+    }
+    bytes.truncate(len);
+    Ok(bytes)
+}
+
+/// Decodes rows from pages, starting at a position
+struct RowReader<'a> {
+    pager: &'a Pager,
+    layout: Layout,
+    schema: &'a TableSchema,
+    bytes: Vec<u8>,
+    offset: usize,
+    /// Read pages with offsets in `bytes` where their content starts and the number of bytes of
+    /// their content dropped from `bytes`
+    pages: Vec<(usize, u32, usize)>,
+    next: Option<u32>,
+    // A chain visits every page at most once, a longer one has a loop
+    remaining: u32,
+    last: Range<usize>,
+}
+
+impl<'a> RowReader<'a> {
+    fn new(stored: &'a StoredRows, schema: &'a TableSchema, start: Position) -> Result<Self, Error> {
+        let mut reader = Self {
+            pager: &stored.pager,
+            layout: stored.layout,
+            schema,
+            bytes: vec![],
+            offset: start.offset,
+            pages: vec![],
+            next: Some(start.page),
+            remaining: stored.pager.page_count(),
+            last: 0..0,
+        };
+        // Position right after the content of a page is the start of the next page
+        while reader.pages.is_empty() || reader.bytes.len() < reader.offset {
+            if !reader.read_page()? {
+                return Err(reader.corrupted("row is out of the file"));
+            }
+        }
+        Ok(reader)
+    }
+
+    fn corrupted(&self, detail: &str) -> Error {
+        Error::CorruptedTable {path: self.pager.path().to_string(), detail: format!("table `{}`: {detail}", self.schema.name)}
+    }
+
+    fn read_page(&mut self) -> Result<bool, Error> {
+        let page = match self.next {
+            Some(page) => page,
+            None => return Ok(false),
+        };
+        if self.remaining == 0 {
+            return Err(Error::CorruptedFile {path: self.pager.path().to_string(), detail: "loop in a chain of pages".to_string()});
+        }
+        self.remaining -= 1;
+        let content = self.pager.content(page, self.layout)?;
+        self.pages.push((self.bytes.len(), page, 0));
+        self.bytes.extend_from_slice(content.bytes());
+        self.next = content.next();
+        Ok(true)
+    }
+
+    fn position(&self, offset: usize) -> Position {
+        let (start, page, dropped) = *self.pages.iter().rev().find(|(start, ..)| *start <= offset).unwrap();
+        Position {page, offset: dropped + offset - start}
+    }
+
+    /// Decodes the next row and returns it with its position
+    fn next_row(&mut self) -> Result<(Row, Position), Error> {
+        loop {
+            let mut end = self.offset;
+            if let Some(row) = decode_row(self.schema, &self.bytes, &mut end) {
+                let position = self.position(self.offset);
+                self.last = self.offset..end;
+                self.offset = end;
+                return Ok((row, position));
+            }
+
+            // Row continues on the next page, read bytes before it are dropped
+            let offset = self.offset;
+            self.bytes.drain(..offset);
+            self.offset = 0;
+            let first = self.pages.iter().rposition(|(start, ..)| *start <= offset).unwrap();
+            self.pages.drain(..first);
+            for (start, _, dropped) in &mut self.pages {
+                if *start <= offset {
+                    *dropped += offset - *start;
+                    *start = 0;
+                } else {
+                    *start -= offset;
+                }
+            }
+            if !self.read_page()? {
+                return Err(self.corrupted("invalid row or fewer rows than the header says"));
+            }
+        }
+    }
+
+    /// Encoded bytes of the last decoded row
+    fn last_bytes(&self) -> &[u8] {
+        &self.bytes[self.last.clone()]
+    }
+}
@@ -1,4 +1,5 @@
 #[cfg(test)]
+#[allow(clippy::module_inception, clippy::assertions_on_constants, clippy::len_zero)]
 mod tests {
     use crate::*;
     
@@ -21,57 +22,51 @@ mod tests {
     }
 
     #[test]
-    #[should_panic(expected = "ERROR: table name can't be empty: ./src/tests_input/schema_with_empty_table_name.tbls")]
     fn schema_with_empty_table_name() {
         let schema = parse_table_schema("./src/tests_input/schema_with_empty_table_name.tbls");
-        if let Err(ref err) = schema {
-            assert!(false, "{}", err);
-        }
+        assert!(matches!(schema, Err(Error::SchemaParse {line: 1, kind: SchemaParseError::EmptyTableName, ..})));
     } 
 
     #[test]
-    #[should_panic(expected = "ERROR: column with name 'id' already exists in table scheme: ./src/tests_input/double_column_declaration.tbls")]
     fn double_column_declaration() {
         let schema = parse_table_schema("./src/tests_input/double_column_declaration.tbls");
-        if let Err(ref err) = schema {
-            assert!(false, "{}", err);
+        match schema {
+            Err(Error::SchemaParse {line: 4, kind: SchemaParseError::DuplicateColumn(col), ..}) => assert!(col == "id"),
+            _ => assert!(false, "expected duplicate column error"),
         }
     }
 
     #[test]
-    #[should_panic(expected = "ERROR: unknown column type at line 1 in a file ./src/tests_input/invalid_column_type.tbls")]
     fn invalid_column_type() {
         let schema = parse_table_schema("./src/tests_input/invalid_column_type.tbls");
-        if let Err(ref err) = schema {
-            assert!(false, "{}", err);
+        match schema {
+            Err(Error::SchemaParse {line: 2, kind: SchemaParseError::UnknownType(name), ..}) => assert!(name == "8shfj"),
+            _ => assert!(false, "expected unknown type error"),
         }
     }
     
     #[test]
-    #[should_panic(expected = "ERROR: table name not provided in a file ./src/tests_input/empty_table_schema.tbls")]
     fn empty_table_schema() {
         let schema = parse_table_schema("./src/tests_input/empty_table_schema.tbls");
-        if let Err(ref err) = schema {
-            assert!(false, "{}", err);
-        }
+        assert!(matches!(schema, Err(Error::SchemaParse {line: 1, kind: SchemaParseError::MissingTableName, ..})));
     }
     
     #[test]
-    #[should_panic(expected = "ERROR: invalid format for column at line 1 in a file: ./src/tests_input/invalid_column_format.tbls")]
     fn invalid_column_format() {
         let schema = parse_table_schema("./src/tests_input/invalid_column_format.tbls");
-        if let Err(ref err) = schema {
-            assert!(false, "{}", err);
-        }
+        assert!(matches!(schema, Err(Error::SchemaParse {line: 2, kind: SchemaParseError::InvalidColumnFormat, ..})));
     }
     
     #[test]
-    #[should_panic(expected = "ERROR: empty column name at line 1 in a file ./src/tests_input/empty_column_name.tbls")]
     fn empty_column_name() {
         let schema = parse_table_schema("./src/tests_input/empty_column_name.tbls");
-        if let Err(ref err) = schema {
-            assert!(false, "{}", err);
-        }
+        assert!(matches!(schema, Err(Error::SchemaParse {line: 2, kind: SchemaParseError::EmptyColumnName, ..})));
+    }
+
+    #[test]
+    fn missing_schema_file() {
+        let schema = parse_table_schema("./src/tests_input/missing.tbls");
+        assert!(matches!(schema, Err(Error::Io {action: "open", ..})));
     }
 
     // --- parse_query() ---
@@ -87,7 +82,10 @@ mod tests {
             Op::Select,
         ];
         match parse_query(query) {
-            Ok(tokens) => assert!(expected == tokens),
+            Ok(tokens) => {
+                assert!(expected == tokens.iter().map(|token| token.op.clone()).collect::<Vec<Op>>());
+                assert!(tokens.iter().map(|token| token.pos).collect::<Vec<usize>>() == vec![0, 3, 6, 8, 11, 16]);
+            },
             Err(err)   => assert!(false, "{}", err),
         }
         
@@ -102,31 +100,30 @@ mod tests {
             Op::Delete,
         ];
         match parse_query(query) {
-            Ok(tokens) => assert!(expected == tokens),
+            Ok(tokens) => assert!(expected == tokens.into_iter().map(|token| token.op).collect::<Vec<Op>>()),
             Err(err)   => assert!(false, "{}", err),
         }
     }
 
     #[test]
-    #[should_panic(expected = "ERROR: unclosed string literal in a query")]
     fn unclosed_string() {
         let query = "3 \"John Watson 20 insert";
-        if let Err(err) = parse_query(query) {
-            assert!(false, "{}", err);
-        }
+        assert!(matches!(parse_query(query), Err(Error::UnclosedString {pos: 2})));
     }
 
     // --- logical_op_check() ---
+    fn word(data_type: DataType, value: WordType, pos: usize) -> Word {
+        Word {data_type, value, pos}
+    }
+
     #[test]
     fn valid_logical_op() {
-        let words = vec![ 
-            (DataType::Str, WordType::Str("name".to_string())), 
-            (DataType::Str, WordType::Str("John".to_string())),
-        ];
         let table = Table {
             schema: TableSchema {
                 name: "test".to_string(),
-                cols: vec![Col {name: "name".to_string(), data_type: DataType::Str}],
+                cols: vec![Col::plain("name".to_string(), DataType::Str, false)],
+                indexes: vec![],
+                sequence: 1,
             },
             rows: vec![],
         };
@@ -135,84 +132,88 @@ mod tests {
             value: WordType::Str("John".to_string()),
             op: Op::Equal,
         };
-        assert!(expected == logical_op_check(Op::Equal, words[0].1.clone(), words[1].clone(), &table).unwrap());
+        let col = word(DataType::Str, WordType::Str("name".to_string()), 0);
+        let value = word(DataType::Str, WordType::Str("John".to_string()), 5);
+        assert!(expected == logical_op_check(Op::Equal, col, value, &table).unwrap());
     }
 
     #[test]
-    #[should_panic(expected = "ERROR: invalid argument for `>` operation, expected string but found Int(10)")]
     fn not_string_for_col_name() {
-        let words = vec![
-            (DataType::Int, WordType::Int(10)), 
-            (DataType::Int, WordType::Int(5))
-        ];
         let table = Table {
             schema: TableSchema {
                 name: "test".to_string(),
                 cols: vec![],
+                indexes: vec![],
+                sequence: 1,
             },
             rows: vec![],
         };
-        if let Err(err) = logical_op_check(Op::More, words[0].1.clone(), words[1].clone(), &table) {
-            assert!(false, "{}", err);
-        }
+        let col = word(DataType::Int, WordType::Int(10), 0);
+        let value = word(DataType::Int, WordType::Int(5), 3);
+        let result = logical_op_check(Op::More, col, value, &table);
+        assert!(matches!(result, Err(Error::UnexpectedWord {found: WordType::Int(10), pos: 0, ..})));
     }
     
     #[test]
-    #[should_panic(expected = "ERROR: no such column `age` in table `test`")]
     fn not_existing_column() {
-        let words = vec![
-            (DataType::Str, WordType::Str("age".to_string())), 
-            (DataType::Int, WordType::Int(5))
-        ];
         let table = Table {
             schema: TableSchema {
                 name: "test".to_string(),
-                cols: vec![Col {name: "id".to_string(), data_type: DataType::Int}],
+                cols: vec![Col::plain("id".to_string(), DataType::Int, false)],
+                indexes: vec![],
+                sequence: 1,
             },
             rows: vec![],
         };
-        if let Err(err) = logical_op_check(Op::More, words[0].1.clone(), words[1].clone(), &table) {
-            assert!(false, "{}", err);
+        let col = word(DataType::Str, WordType::Str("age".to_string()), 0);
+        let value = word(DataType::Int, WordType::Int(5), 4);
+        match logical_op_check(Op::More, col, value, &table) {
+            Err(Error::UnknownColumn {table, column, pos: 0}) => assert!(table == "test" && column == "age"),
+            _ => assert!(false, "expected unknown column error"),
         }
     }
     
     #[test]
-    #[should_panic(expected = "ERROR: invalid argument for `>` operation expected type Int but found type Str(\"8\")")]
     fn types_mismatch_between_col_and_word() {
-        let words = vec![
-            (DataType::Str, WordType::Str("id".to_string())), 
-            (DataType::Str, WordType::Str("8".to_string()))
-        ];
         let table = Table {
             schema: TableSchema {
                 name: "test".to_string(),
-                cols: vec![Col {name: "id".to_string(), data_type: DataType::Int}],
+                cols: vec![Col::plain("id".to_string(), DataType::Int, false)],
+                indexes: vec![],
+                sequence: 1,
             },
             rows: vec![],
         };
-        if let Err(err) = logical_op_check(Op::More, words[0].1.clone(), words[1].clone(), &table) {
-            assert!(false, "{}", err);
-        }
+        let col = word(DataType::Str, WordType::Str("id".to_string()), 0);
+        let value = word(DataType::Str, WordType::Str("8".to_string()), 3);
+        let result = logical_op_check(Op::More, col, value, &table);
+        assert!(matches!(result, Err(Error::TypeMismatch {expected: DataType::Int, found: DataType::Str, pos: 3, ..})));
     }
 
     #[test]
     fn create_table() {
         let query = "id Int name Str clients create";
         let mut database = Database {
-            name: "database".to_string(),
             path: String::new(),
+            format: Format::Directory,
+            paged: PagedTables::new(),
+            transaction: None,
             tables: vec![],
+            quarantined: vec![],
+            warnings: vec![],
         }; 
         let result = execute_query(query, &mut database);
-        assert!(result == Ok(None));
+        assert!(matches!(result, Ok(None)));
         assert!(database.tables.len() == 1);
         let expected = Table {
             schema: TableSchema {
                 name: "clients".to_string(),
                 cols: vec![
-                    Col {name: "id".to_string(), data_type: DataType::Int},
-                    Col {name: "name".to_string(), data_type: DataType::Str},
+                    Col::plain("id".to_string(), DataType::Int, false),
+                    Col::plain("name".to_string(), DataType::Str, false),
                 ],
+                indexes: vec![],
+                sequence: 1,
             },
             rows: vec![],
         };
@@ -223,44 +224,56 @@ mod tests {
     fn drop_table() {
         let query = "clients drop";
         let mut database = Database {
-            name: "database".to_string(),
             path: String::new(),
+            format: Format::Directory,
+            paged: PagedTables::new(),
+            transaction: None,
             tables: vec![
                 Table {
                     schema: TableSchema {
                         name: "clients".to_string(),
                         cols: vec![],
+                        indexes: vec![],
+                        sequence: 1,
                     },
                     rows: vec![],
                 },
             ],
+            quarantined: vec![],
+            warnings: vec![],
         }; 
         let result = execute_query(query, &mut database);
-        assert!(result == Ok(None));
+        assert!(matches!(result, Ok(None)));
         assert!(database.tables.len() == 0);
     }
 
     #[test]
     fn select_and_insert() {
         let mut database = Database {
-            name: "database".to_string(),
             path: String::new(),
+            format: Format::Directory,
+            paged: PagedTables::new(),
+            transaction: None,
             tables: vec![],
+            quarantined: vec![],
+            warnings: vec![],
         };
 
-        assert!(execute_query("id Int name Str age Int clients create", &mut database) == Ok(None));
-        assert!(execute_query("0 John 20 clients insert", &mut database) == Ok(None));
-        assert!(execute_query("1 Emily 25 clients insert", &mut database) == Ok(None));
-        assert!(execute_query("2 Alex 19 clients insert", &mut database) == Ok(None));
+        assert!(matches!(execute_query("id Int name Str age Int clients create", &mut database), Ok(None)));
+        assert!(matches!(execute_query("0 John 20 clients insert", &mut database), Ok(None)));
+        assert!(matches!(execute_query("1 Emily 25 clients insert", &mut database), Ok(None)));
+        assert!(matches!(execute_query("2 Alex 19 clients insert", &mut database), Ok(None)));
 
         let expected = Table {
             schema: TableSchema {
                 name: "temp".to_string(),
                 cols: vec![
-                    Col {name: "id".to_string(), data_type: DataType::Int},
-                    Col {name: "name".to_string(), data_type: DataType::Str},
-                    Col {name: "age".to_string(), data_type: DataType::Int},
+                    Col::plain("id".to_string(), DataType::Int, false),
+                    Col::plain("name".to_string(), DataType::Str, false),
+                    Col::plain("age".to_string(), DataType::Int, false),
                 ],
+                indexes: vec![],
+                sequence: 1,
             },
             rows: vec![
                 vec![WordType::Int(0), WordType::Str("John".to_string()), WordType::Int(20)],
@@ -275,23 +288,29 @@ mod tests {
     #[test]
     fn select_with_filter() {
         let mut database = Database {
-            name: "database".to_string(),
             path: String::new(),
+            format: Format::Directory,
+            paged: PagedTables::new(),
+            transaction: None,
             tables: vec![],
+            quarantined: vec![],
+            warnings: vec![],
         };
 
-        assert!(execute_query("id Int name Str age Int clients create", &mut database) == Ok(None));
-        assert!(execute_query("0 John 20 clients insert", &mut database) == Ok(None));
-        assert!(execute_query("1 John 25 clients insert", &mut database) == Ok(None));
-        assert!(execute_query("2 Alex 20 clients insert", &mut database) == Ok(None));
+        assert!(matches!(execute_query("id Int name Str age Int clients create", &mut database), Ok(None)));
+        assert!(matches!(execute_query("0 John 20 clients insert", &mut database), Ok(None)));
+        assert!(matches!(execute_query("1 John 25 clients insert", &mut database), Ok(None)));
+        assert!(matches!(execute_query("2 Alex 20 clients insert", &mut database), Ok(None)));
 
         let expected = Table {
             schema: TableSchema {
                 name: "temp".to_string(),
                 cols: vec![
-                    Col {name: "id".to_string(), data_type: DataType::Int},
-                    Col {name: "name".to_string(), data_type: DataType::Str},
+                    Col::plain("id".to_string(), DataType::Int, false),
+                    Col::plain("name".to_string(), DataType::Str, false),
                 ],
+                indexes: vec![],
+                sequence: 1,
             },
             rows: vec![
                 vec![WordType::Int(1), WordType::Str("John".to_string())],
@@ -304,16 +323,20 @@ mod tests {
     #[test]
     fn save_and_load_database() {
         let expected = Database {
-            name: "database".to_string(),
             path: String::new(),
+            format: Format::Directory,
+            paged: PagedTables::new(),
+            transaction: None,
             tables: vec![
                 Table {
                     schema: TableSchema {
                         name: "table1".to_string(),
                         cols: vec![
-                            Col {name: "id".to_string(), data_type: DataType::Int},
-                            Col {name: "name".to_string(), data_type: DataType::Str},
+                            Col::plain("id".to_string(), DataType::Int, false),
+                            Col::plain("name".to_string(), DataType::Str, false),
                         ],
+                        indexes: vec![],
+                        sequence: 1,
                     },
                     rows: vec![
                         vec![
@@ -330,10 +353,12 @@ mod tests {
                     schema: TableSchema {
                         name: "table2".to_string(),
                         cols: vec![
-                            Col {name: "id".to_string(), data_type: DataType::Int},
-                            Col {name: "name".to_string(), data_type: DataType::Str},
-                            Col {name: "age".to_string(), data_type: DataType::Int},
+                            Col::plain("id".to_string(), DataType::Int, false),
+                            Col::plain("name".to_string(), DataType::Str, false),
+                            Col::plain("age".to_string(), DataType::Int, false),
                         ],
+                        indexes: vec![],
+                        sequence: 1,
                     },
                     rows: vec![
                         vec![
@@ -348,10 +373,11 @@ mod tests {
                         ],
                     ],
                 },
-            ]
+            ],
+            quarantined: vec![],
+            warnings: vec![],
         };
-        save_database_to("./src/tests_input/database", &expected).unwrap();
-        let database = load_database_from("./src/tests_input/database").unwrap();
+        let database = load_database_from("./src/tests_input/database", false).unwrap();
         'outer: for table in &expected.tables {
             for table1 in &database.tables {
                 if table.schema.name == table1.schema.name {
@@ -362,4 +388,1466 @@ mod tests {
             assert!(false);
         }
     }
+
+    #[test]
+    fn save_database_in_current_format() {
+        let path = clean_test_dir("save_and_load_database");
+        let expected = load_database_from("./src/tests_input/database", false).unwrap();
+        save_database_to(&path, &expected).unwrap();
+        let database = load_database_from(&path, false).unwrap();
+        assert!(database.paged.tables.len() == 2);
+        assert_same_tables(&expected, &database);
+    }
+
+    // --- helpers ---
+    fn col(name: &str, data_type: DataType) -> Col {
+        Col::plain(name.to_string(), data_type, false)
+    }
+
+    fn table(name: &str, cols: Vec<Col>, rows: Vec<Row>) -> Table {
+        Table {
+            schema: TableSchema {
+                name: name.to_string(),
+                cols,
+                indexes: vec![],
+                sequence: 1,
+            },
+            rows,
+        }
+    }
+
+    /// Rows of the table, also of a table stored in pages
+    fn rows(database: &Database, table_idx: usize) -> Vec<Row> {
+        read_table(database, table_idx).unwrap().rows.clone()
+    }
+
+    fn text(value: &str) -> WordType {
+        WordType::Str(value.to_string())
+    }
+
+    fn memory_database() -> Database {
+        Database {
+            path: String::new(),
+            format: Format::Directory,
+            paged: PagedTables::new(),
+            transaction: None,
+            tables: vec![],
+            quarantined: vec![],
+            warnings: vec![],
+        }
+    }
+
+    #[test]
+    fn update_with_condition() {
+        let mut database = memory_database();
+
+        assert!(matches!(execute_query("id Int name Str age Int clients create", &mut database), Ok(None)));
+        assert!(matches!(execute_query("0 John 20 clients insert", &mut database), Ok(None)));
+        assert!(matches!(execute_query("1 Emily 25 clients insert", &mut database), Ok(None)));
+        assert!(matches!(execute_query("2 Alex 19 clients insert", &mut database), Ok(None)));
+
+        let updated = execute_query("age 20 > name Kate age 30 clients update", &mut database).unwrap().unwrap();
+        assert!(updated.rows == vec![vec![WordType::Int(2)]]);
+
+        let expected = vec![
+            vec![WordType::Int(0), WordType::Str("Kate".to_string()), WordType::Int(30)],
+            vec![WordType::Int(1), WordType::Str("Kate".to_string()), WordType::Int(30)],
+            vec![WordType::Int(2), WordType::Str("Alex".to_string()), WordType::Int(19)],
+        ];
+        assert!(expected == database.tables[0].rows);
+    }
+
+    #[test]
+    fn update_type_mismatch() {
+        let mut database = memory_database();
+
+        assert!(matches!(execute_query("id Int name Str clients create", &mut database), Ok(None)));
+        assert!(matches!(execute_query("0 John clients insert", &mut database), Ok(None)));
+        let result = execute_query("id 0 == id John clients update", &mut database);
+        assert!(matches!(result, Err(Error::TypeMismatch {expected: DataType::Int, found: DataType::Str, pos: 11, ..})));
+    }
+
+    #[test]
+    fn order_by() {
+        let mut database = memory_database();
+
+        assert!(matches!(execute_query("id Int name Str age Int clients create", &mut database), Ok(None)));
+        assert!(matches!(execute_query("0 John 20 clients insert 1 Emily 25 clients insert", &mut database), Ok(None)));
+        assert!(matches!(execute_query("2 Alex 20 clients insert 3 Emily 19 clients insert", &mut database), Ok(None)));
+
+        let table = execute_query("id name clients select name asc id desc order-by", &mut database).unwrap().unwrap();
+        let ids = (0..table.len()).map(|row| table.get_int(row, "id").unwrap()).collect::<Vec<i32>>();
+        assert!(ids == vec![2, 3, 1, 0]);
+
+        // Rows with equal keys keep storage order
+        let table = execute_query("age id clients select age order-by", &mut database).unwrap().unwrap();
+        let ids = (0..table.len()).map(|row| table.get_int(row, "id").unwrap()).collect::<Vec<i32>>();
+        assert!(ids == vec![3, 0, 2, 1]);
+
+        let table = execute_query("age 20 > id clients select id desc order-by", &mut database).unwrap().unwrap();
+        let ids = (0..table.len()).map(|row| table.get_int(row, "id").unwrap()).collect::<Vec<i32>>();
+        assert!(ids == vec![2, 1, 0]);
+
+        assert!(matches!(execute_query("id desc order-by", &mut database), Err(Error::MissingSelect {pos: 8, ..})));
+        assert!(matches!(
+            execute_query("id clients select age asc order-by", &mut database),
+            Err(Error::UnknownColumn {pos: 18, ..}),
+        ));
+    }
+
+    #[test]
+    fn limit_and_offset() {
+        let mut database = memory_database();
+
+        assert!(matches!(execute_query("id Int clients create", &mut database), Ok(None)));
+        for id in 0..10 {
+            assert!(matches!(execute_query(&format!("{id} clients insert"), &mut database), Ok(None)));
+        }
+        let ids = |table: Table| (0..table.len()).map(|row| table.get_int(row, "id").unwrap()).collect::<Vec<i32>>();
+
+        let table = execute_query("id clients select 3 limit", &mut database).unwrap().unwrap();
+        assert!(ids(table) == vec![0, 1, 2]);
+        let table = execute_query("id 2 > id clients select 4 offset 2 limit", &mut database).unwrap().unwrap();
+        assert!(ids(table) == vec![6, 7]);
+        let table = execute_query("id clients select 5 limit 3 offset", &mut database).unwrap().unwrap();
+        assert!(ids(table) == vec![3, 4]);
+        let table = execute_query("id clients select id desc order-by 1 offset 2 limit", &mut database).unwrap().unwrap();
+        assert!(ids(table) == vec![8, 7]);
+        let table = execute_query("id clients select 20 offset", &mut database).unwrap().unwrap();
+        assert!(table.is_empty());
+
+        assert!(matches!(execute_query("3 limit", &mut database), Err(Error::MissingSelect {op: "limit", ..})));
+        assert!(matches!(
+            execute_query("id clients select -1 limit", &mut database),
+            Err(Error::UnexpectedWord {found: WordType::Int(-1), pos: 18, ..}),
+        ));
+    }
+
+    #[test]
+    fn aggregates() {
+        let mut database = memory_database();
+
+        assert!(matches!(execute_query("id Int name Str age Int clients create", &mut database), Ok(None)));
+        assert!(matches!(execute_query("0 John 20 clients insert 1 Emily 25 clients insert", &mut database), Ok(None)));
+        assert!(matches!(execute_query("2 Alex 19 clients insert 3 Bob 30 clients insert", &mut database), Ok(None)));
+
+        let table = execute_query("age 20 > name age clients select count age sum age avg name min name max", &mut database).unwrap().unwrap();
+        let names = table.columns().iter().map(Col::name).collect::<Vec<_>>();
+        assert!(names == vec!["count", "sum-age", "avg-age", "min-name", "max-name"]);
+        assert!(table.columns()[3].data_type() == DataType::Str);
+        assert!(table.len() == 1);
+        assert!(table.get_int(0, "count") == Some(3));
+        assert!(table.get_int(0, "sum-age") == Some(75));
+        assert!(table.get_float(0, "avg-age") == Some(25.0));
+        assert!(table.get_str(0, "min-name") == Some("Bob"));
+        assert!(table.get_str(0, "max-name") == Some("John"));
+
+        let table = execute_query("age 100 > id clients select count", &mut database).unwrap().unwrap();
+        assert!(table.get_int(0, "count") == Some(0));
+
+        assert!(matches!(
+            execute_query("name clients select name sum", &mut database),
+            Err(Error::NotNumeric {op: "sum", found: DataType::Str, pos: 20, ..}),
+        ));
+        assert!(matches!(
+            execute_query("name clients select age max", &mut database),
+            Err(Error::UnknownColumn {pos: 20, ..}),
+        ));
+        let table = execute_query("age 100 > age clients select age min age sum", &mut database).unwrap().unwrap();
+        assert!(table.get(0, "min-age") == Some(&WordType::Null));
+        assert!(table.get(0, "sum-age") == Some(&WordType::Null));
+    }
+
+    #[test]
+    fn group_by_and_having() {
+        let mut database = memory_database();
+
+        assert!(matches!(execute_query("id Int city Str age Int clients create", &mut database), Ok(None)));
+        assert!(matches!(execute_query("0 Paris 20 clients insert 1 Rome 25 clients insert", &mut database), Ok(None)));
+        assert!(matches!(execute_query("2 Paris 30 clients insert 3 Oslo 19 clients insert", &mut database), Ok(None)));
+        assert!(matches!(execute_query("4 Rome 35 clients insert", &mut database), Ok(None)));
+
+        let table = execute_query("city age clients select count age sum city group-by", &mut database).unwrap().unwrap();
+        let names = table.columns().iter().map(Col::name).collect::<Vec<_>>();
+        assert!(names == vec!["city", "count", "sum-age"]);
+        let groups = (0..table.len())
+            .map(|row| (table.get_str(row, "city").unwrap(), table.get_int(row, "count").unwrap(), table.get_int(row, "sum-age").unwrap()))
+            .collect::<Vec<_>>();
+        assert!(groups == vec![("Paris", 2, 50), ("Rome", 2, 60), ("Oslo", 1, 19)]);
+
+        let query = "city age clients select age max city group-by \"max-age\" 30 > having max-age desc order-by";
+        let table = execute_query(query, &mut database).unwrap().unwrap();
+        assert!(table.len() == 2);
+        assert!(table.get_str(0, "city") == Some("Rome") && table.get_int(0, "max-age") == Some(35));
+        assert!(table.get_str(1, "city") == Some("Paris"));
+
+        let table = execute_query("city age clients select count city age group-by \"count\" 1 > having", &mut database).unwrap().unwrap();
+        assert!(table.len() == 5);
+
+        // Name of an aggregate result is a column name in conditions
+        let table = execute_query("city clients select count city group-by count 2 == having", &mut database).unwrap().unwrap();
+        let cities = (0..table.len()).map(|row| table.get_str(row, "city").unwrap()).collect::<Vec<_>>();
+        assert!(cities == vec!["Paris", "Rome"]);
+
+        assert!(matches!(
+            execute_query("city clients select age group-by", &mut database),
+            Err(Error::UnknownColumn {pos: 20, ..}),
+        ));
+        assert!(matches!(execute_query("city clients select having", &mut database), Err(Error::InvalidConditions {count: 0, ..})));
+    }
+
+    #[test]
+    fn inner_and_left_join() {
+        let mut database = memory_database();
+
+        assert!(matches!(execute_query("id Int name Str clients create id Int client Int total Int orders create", &mut database), Ok(None)));
+        assert!(matches!(execute_query("0 John clients insert 1 Emily clients insert 2 Alex clients insert", &mut database), Ok(None)));
+        assert!(matches!(execute_query("0 1 10 orders insert 1 0 20 orders insert 2 1 30 orders insert", &mut database), Ok(None)));
+
+        let query = "clients.name orders.total clients orders id client join";
+        let table = execute_query(query, &mut database).unwrap().unwrap();
+        let names = table.columns().iter().map(Col::name).collect::<Vec<_>>();
+        assert!(names == vec!["clients.name", "orders.total"]);
+        let rows = (0..table.len())
+            .map(|row| (table.get_str(row, "clients.name").unwrap(), table.get_int(row, "orders.total").unwrap()))
+            .collect::<Vec<_>>();
+        assert!(rows == vec![("John", 20), ("Emily", 10), ("Emily", 30)]);
+
+        let query = "orders.total 15 > clients.name orders.total clients orders id client join";
+        let table = execute_query(query, &mut database).unwrap().unwrap();
+        assert!(table.len() == 2);
+
+        let table = execute_query("* clients orders id client left-join", &mut database).unwrap().unwrap();
+        assert!(table.columns().len() == 5);
+        assert!(table.len() == 4);
+        assert!(table.get_str(3, "clients.name") == Some("Alex"));
+        assert!(table.get(3, "orders.total") == Some(&WordType::Null));
+        assert!(table.columns()[4].nullable());
+
+        assert!(matches!(
+            execute_query("* clients orders name client join", &mut database),
+            Err(Error::TypeMismatch {expected: DataType::Str, found: DataType::Int, ..}),
+        ));
+        assert!(matches!(
+            execute_query("* clients orders id buyer join", &mut database),
+            Err(Error::UnknownColumn {pos: 20, ..}),
+        ));
+    }
+
+    #[test]
+    fn query_error_positions() {
+        let mut database = memory_database();
+
+        assert!(matches!(execute_query("id Int name Str clients create", &mut database), Ok(None)));
+        assert!(matches!(execute_query("id Int clients create", &mut database), Err(Error::TableExists {pos: 7, ..})));
+        assert!(matches!(
+            execute_query("John clients insert", &mut database),
+            Err(Error::StackUnderflow {op: "insert", needed: 2, found: 1, pos: 13}),
+        ));
+        assert!(matches!(
+            execute_query("id age clients select", &mut database),
+            Err(Error::UnknownColumn {pos: 3, ..}),
+        ));
+        assert!(matches!(execute_query("id 0 == id 1 == clients delete", &mut database), Err(Error::InvalidConditions {count: 2, pos: 24})));
+        assert!(matches!(execute_query("id 0 == and clients delete", &mut database), Err(Error::StackUnderflow {op: "and", pos: 8, ..})));
+        assert!(matches!(execute_query("id 0 == orders delete", &mut database), Err(Error::UnknownTable {pos: 8, ..})));
+        assert!(matches!(execute_query("kind Type kinds create", &mut database), Err(Error::UnsupportedType {..})));
+
+        // Leftovers of a successful query are reported as warnings
+        assert!(execute_query("5 id clients select id 0 ==", &mut database).unwrap().is_some());
+        assert!(database.warnings == vec![Warning::UnusedWords {count: 1}, Warning::UnusedConditions {count: 1}]);
+    }
+
+    // --- crash safety ---
+    fn clean_test_dir(name: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("rosemary-{name}"));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir.display().to_string()
+    }
+
+    fn clients_database(path: &str) -> Database {
+        let mut database = memory_database();
+        database.path = path.to_string();
+        database.tables.push(table(
+            "clients",
+            vec![col("id", DataType::Int), col("name", DataType::Str)],
+            vec![vec![WordType::Int(0), text("John")]],
+        ));
+        database
+    }
+
+    fn assert_same_tables(expected: &Database, loaded: &Database) {
+        assert!(expected.tables.len() == loaded.tables.len());
+        for (idx, table) in expected.tables.iter().enumerate() {
+            let same = table_idx_by_name(loaded, &table.schema.name)
+                .is_some_and(|loaded_idx| loaded.tables[loaded_idx].schema == table.schema && rows(loaded, loaded_idx) == rows(expected, idx));
+            assert!(same, "table `{}` differs", table.schema.name);
+        }
+    }
+
+    #[test]
+    fn crash_before_checkpoint_file() {
+        let path = clean_test_dir("crash_before_checkpoint_file");
+        let mut database = clients_database(&path);
+        save_database_to(&path, &database).unwrap();
+
+        execute_query("1 Emily clients insert", &mut database).unwrap();
+        let temp_path = write_temp_file(&format!("{path}/clients.tbl"), &encode_table(&database.tables[0]).unwrap()).unwrap();
+        // Crash: the checkpoint file is never written, the new table file is discarded
+
+        let loaded = load_database_from(&path, false).unwrap();
+        assert_same_tables(&database, &loaded);
+        assert!(!Path::new(&temp_path).exists());
+    }
+
+    #[test]
+    fn crash_after_checkpoint_file() {
+        let path = clean_test_dir("crash_after_checkpoint_file");
+        let mut database = clients_database(&path);
+        save_database_to(&path, &database).unwrap();
+
+        execute_query("id Int orders create", &mut database).unwrap();
+        execute_query("7 orders insert", &mut database).unwrap();
+        execute_query("1 Emily clients insert", &mut database).unwrap();
+        prepare_checkpoint(&path, &database).unwrap();
+        // Crash: the new table files are never moved in place and the log isn't emptied
+
+        let loaded = load_database_from(&path, false).unwrap();
+        assert_same_tables(&database, &loaded);
+        assert!(wal::size(&wal::log_path(&path)) == 0);
+        assert!(!Path::new(&format!("{path}/{CHECKPOINT_FILE}")).exists());
+    }
+
+    #[test]
+    fn replay_log_without_checkpoint() {
+        let path = clean_test_dir("replay_log_without_checkpoint");
+        let mut database = load_database_from(&path, false).unwrap();
+
+        assert!(matches!(execute_query("id Int name Str clients create", &mut database), Ok(None)));
+        assert!(matches!(execute_query("id Int orders create", &mut database), Ok(None)));
+        assert!(matches!(execute_query("0 John clients insert", &mut database), Ok(None)));
+        assert!(matches!(execute_query("1 Emily clients insert", &mut database), Ok(None)));
+        assert!(matches!(execute_query("2 Alex clients insert", &mut database), Ok(None)));
+        assert!(matches!(execute_query("id 1 == clients delete", &mut database), Ok(None)));
+        execute_query("id 2 == name Kate clients update", &mut database).unwrap();
+        assert!(matches!(execute_query("orders drop", &mut database), Ok(None)));
+
+        let mut loaded = load_database_from(&path, false).unwrap();
+        assert_same_tables(&database, &loaded);
+
+        checkpoint_database(&mut loaded).unwrap();
+        assert!(wal::size(&wal::log_path(&path)) == 0);
+        assert!(!Path::new(&format!("{path}/orders.tbls")).exists());
+        assert_same_tables(&database, &load_database_from(&path, false).unwrap());
+    }
+
+    #[test]
+    fn torn_log_record() {
+        let path = clean_test_dir("torn_log_record");
+        let mut database = load_database_from(&path, false).unwrap();
+        assert!(matches!(execute_query("id Int clients create", &mut database), Ok(None)));
+        assert!(matches!(execute_query("0 clients insert", &mut database), Ok(None)));
+        let log_size = wal::size(&wal::log_path(&path));
+
+        // Crash in the middle of an append
+        let mut log = fs::OpenOptions::new().append(true).open(wal::log_path(&path)).unwrap();
+        log.write_all(&[42, 0, 0, 0, 1, 2]).unwrap();
+
+        let loaded = load_database_from(&path, false).unwrap();
+        assert_same_tables(&database, &loaded);
+        assert!(matches!(loaded.warnings[..], [Warning::IncompleteTransaction {..}]));
+        assert!(wal::size(&wal::log_path(&path)) == log_size);
+    }
+
+    // --- transactions ---
+    #[test]
+    fn commit_and_rollback() {
+        let mut database = memory_database();
+
+        assert!(matches!(execute_query("id Int name Str clients create", &mut database), Ok(None)));
+        assert!(matches!(execute_query("begin 0 John clients insert", &mut database), Ok(None)));
+        assert!(matches!(execute_query("1 Emily clients insert id 0 == clients delete", &mut database), Ok(None)));
+        assert!(matches!(execute_query("rollback", &mut database), Ok(None)));
+        assert!(database.tables[0].rows.is_empty());
+
+        assert!(matches!(execute_query("begin 0 John clients insert commit", &mut database), Ok(None)));
+        assert!(database.tables[0].rows == vec![vec![WordType::Int(0), WordType::Str("John".to_string())]]);
+        assert!(database.transaction.is_none());
+    }
+
+    #[test]
+    fn error_rolls_back_transaction() {
+        let mut database = memory_database();
+
+        assert!(matches!(execute_query("id Int name Str clients create", &mut database), Ok(None)));
+        assert!(matches!(execute_query("begin 0 John clients insert", &mut database), Ok(None)));
+        assert!(matches!(execute_query("clients drop", &mut database), Ok(None)));
+        assert!(execute_query("Emily 1 clients insert", &mut database).is_err());
+        assert!(database.tables.len() == 1);
+        assert!(database.tables[0].rows.is_empty());
+        assert!(matches!(execute_query("commit", &mut database), Err(Error::NoTransaction {op: "commit", pos: 0})));
+
+        // Every query is atomic even without an explicit transaction
+        assert!(execute_query("0 John clients insert 1 clients insert", &mut database).is_err());
+        assert!(database.tables[0].rows.is_empty());
+    }
+
+    #[test]
+    fn begin_after_changes_of_the_query() {
+        let mut database = memory_database();
+
+        assert!(matches!(execute_query("id Int name Str clients create", &mut database), Ok(None)));
+        let result = execute_query("0 John clients insert begin 1 Emily clients insert 2 clients insert", &mut database);
+        assert!(result.is_err() && database.tables[0].rows.is_empty());
+        assert!(matches!(execute_query("0 John clients insert begin 1 Emily clients insert", &mut database), Ok(None)));
+        assert!(matches!(execute_query("rollback", &mut database), Ok(None)));
+        assert!(database.tables[0].rows.is_empty());
+    }
+
+    #[test]
+    fn uncommitted_transaction_is_not_replayed() {
+        let path = clean_test_dir("uncommitted_transaction");
+        let mut database = load_database_from(&path, false).unwrap();
+
+        assert!(matches!(execute_query("id Int clients create", &mut database), Ok(None)));
+        checkpoint_database(&mut database).unwrap();
+        assert!(matches!(execute_query("begin 0 clients insert 1 clients insert", &mut database), Ok(None)));
+        assert!(matches!(execute_query("clients drop", &mut database), Ok(None)));
+        checkpoint_database(&mut database).unwrap();
+        assert!(Path::new(&format!("{path}/clients.tbls")).exists());
+
+        let loaded = load_database_from(&path, false).unwrap();
+        assert!(loaded.tables.len() == 1);
+        assert!(rows(&loaded, 0).is_empty());
+    }
+
+    // --- Connection ---
+    #[test]
+    fn connection_api() {
+        let path = clean_test_dir("connection_api");
+        let mut connection = Connection::open(&path).unwrap();
+        assert!(connection.execute("id Int name Str clients create").unwrap().is_none());
+        assert!(connection.execute("0 John clients insert 1 Emily clients insert").unwrap().is_none());
+        connection.close().unwrap();
+
+        let mut connection = Connection::open(&path).unwrap();
+        let table = connection.execute("id 1 == name id clients select").unwrap().unwrap();
+        assert!(table.len() == 1);
+        assert!(table.columns().iter().map(Col::name).collect::<Vec<_>>() == vec!["name", "id"]);
+        assert!(table.get_str(0, "name") == Some("Emily"));
+        assert!(table.get_int(0, "id") == Some(1));
+        assert!(table.get_int(0, "name").is_none());
+        assert!(table.get(1, "id").is_none());
+
+        let err = connection.execute("orders drop").unwrap_err();
+        assert!(matches!(err, Error::UnknownTable {ref table, pos: 0} if table == "orders"));
+        assert!(err.to_string() == "no such table `orders` at position 0");
+    }
+
+    #[test]
+    fn damaged_table_is_quarantined() {
+        let path = clean_test_dir("damaged_table");
+        let mut connection = Connection::open(&path).unwrap();
+        assert!(connection.execute("id Int clients create id Int orders create").unwrap().is_none());
+        assert!(connection.execute("0 clients insert 7 orders insert").unwrap().is_none());
+        connection.close().unwrap();
+
+        let mut connection = Connection::open(&path).unwrap();
+        assert!(connection.execute("1 orders insert").unwrap().is_none());
+        drop(connection);
+        let orders_path = format!("{path}/orders.tbl");
+        let orders = fs::read(&orders_path).unwrap();
+        fs::write(&orders_path, [1, 2, 3]).unwrap();
+
+        assert!(matches!(Connection::open(&path), Err(Error::CorruptedTable {..})));
+
+        let mut connection = Connection::open_with_quarantine(&path).unwrap();
+        assert!(connection.quarantined_tables().len() == 1);
+        assert!(connection.quarantined_tables()[0].name() == "orders");
+        assert!(matches!(connection.quarantined_tables()[0].error(), Error::CorruptedTable {..}));
+        assert!(connection.execute("id clients select").unwrap().unwrap().len() == 1);
+        assert!(matches!(connection.execute("id orders select"), Err(Error::TableQuarantined {pos: 3, ..})));
+        assert!(matches!(connection.execute("id Int orders create"), Err(Error::TableQuarantined {..})));
+        assert!(connection.execute("1 clients insert").unwrap().is_none());
+        connection.close().unwrap();
+
+        // Files and logged changes of the quarantined table survive until it is repaired
+        fs::write(&orders_path, orders).unwrap();
+        let mut connection = Connection::open(&path).unwrap();
+        assert!(connection.execute("id orders select").unwrap().unwrap().len() == 2);
+        assert!(connection.execute("id clients select").unwrap().unwrap().len() == 2);
+    }
+
+    // --- NULL ---
+    fn nullable_clients(database: &mut Database) {
+        assert!(matches!(execute_query("id Int name Str? age Int? clients create", database), Ok(None)));
+        assert!(matches!(execute_query("0 John null clients insert 1 null 25 clients insert", database), Ok(None)));
+        assert!(matches!(execute_query("2 Alex 19 clients insert", database), Ok(None)));
+    }
+
+    fn ids(table: Table) -> Vec<i32> {
+        (0..table.len()).map(|row| table.get_int(row, "id").unwrap()).collect()
+    }
+
+    #[test]
+    fn null_in_not_nullable_column() {
+        let mut database = memory_database();
+        nullable_clients(&mut database);
+        assert!(matches!(execute_query("null Bob 30 clients insert", &mut database), Err(Error::NullNotAllowed {pos: 0, ..})));
+    }
+
+    #[test]
+    fn comparison_with_null() {
+        let mut database = memory_database();
+        nullable_clients(&mut database);
+        assert!(matches!(
+            execute_query("age null == id clients select", &mut database),
+            Err(Error::TypeMismatch {found: DataType::Null, pos: 4, ..}),
+        ));
+        let table = execute_query("age 20 < id clients select", &mut database).unwrap().unwrap();
+        assert!(ids(table) == vec![2]);
+    }
+
+    #[test]
+    fn is_null_conditions() {
+        let mut database = memory_database();
+        nullable_clients(&mut database);
+        let table = execute_query("age 20 < age is-null or id clients select", &mut database).unwrap().unwrap();
+        assert!(ids(table) == vec![0, 2]);
+        let table = execute_query("name is-not-null id clients select", &mut database).unwrap().unwrap();
+        assert!(ids(table) == vec![0, 2]);
+    }
+
+    #[test]
+    fn null_in_order_by_and_aggregates() {
+        let mut database = memory_database();
+        nullable_clients(&mut database);
+        let table = execute_query("id age clients select age order-by", &mut database).unwrap().unwrap();
+        assert!(ids(table) == vec![0, 2, 1]);
+        let table = execute_query("age clients select count age avg", &mut database).unwrap().unwrap();
+        assert!(table.get_int(0, "count") == Some(3) && table.get_float(0, "avg-age") == Some(22.0));
+    }
+
+    #[test]
+    fn save_and_load_null_values() {
+        let path = clean_test_dir("null_values");
+        let mut database = load_database_from(&path, false).unwrap();
+        nullable_clients(&mut database);
+        let expected = execute_query("* clients select", &mut database).unwrap().unwrap();
+
+        let mut loaded = load_database_from(&path, false).unwrap();
+        assert!(rows(&loaded, 0) == expected.rows);
+        checkpoint_database(&mut loaded).unwrap();
+        assert!(fs::read_to_string(format!("{path}/clients.tbls")).unwrap() == "clients\nid:Int\nname:Str?\nage:Int?\n");
+        let loaded = load_database_from(&path, false).unwrap();
+        assert!(rows(&loaded, 0) == expected.rows);
+        assert!(loaded.tables[0].schema.cols[1].nullable);
+    }
+
+    // --- strings ---
+    #[test]
+    fn string_longer_than_max_length() {
+        let mut database = memory_database();
+        assert!(matches!(execute_query("id Int name Str(5) note Str? notes create", &mut database), Ok(None)));
+        assert!(matches!(execute_query("0 Émile null notes insert", &mut database), Ok(None)));
+        assert!(matches!(
+            execute_query("2 Johnny null notes insert", &mut database),
+            Err(Error::StringTooLong {max_len: 5, pos: 2, ..}),
+        ));
+        assert!(matches!(
+            execute_query("id 0 == name Johnny notes update", &mut database),
+            Err(Error::StringTooLong {max_len: 5, pos: 13, ..}),
+        ));
+    }
+
+    #[test]
+    fn save_and_load_long_strings() {
+        let path = clean_test_dir("long_strings");
+        let mut database = load_database_from(&path, false).unwrap();
+        let long = "ж".repeat(40);
+        assert!(matches!(execute_query("id Int name Str(5) note Str? notes create", &mut database), Ok(None)));
+        assert!(matches!(execute_query(&format!("0 Émile \"{long}\" notes insert 1 \"\" null notes insert"), &mut database), Ok(None)));
+        checkpoint_database(&mut database).unwrap();
+        assert!(fs::read_to_string(format!("{path}/notes.tbls")).unwrap() == "notes\nid:Int\nname:Str(5)\nnote:Str?\n");
+
+        let loaded = load_database_from(&path, false).unwrap();
+        assert!(loaded.tables[0].schema.cols[1].max_len == Some(5));
+        assert!(rows(&loaded, 0) == vec![
+            vec![WordType::Int(0), WordType::Str("Émile".to_string()), WordType::Str(long)],
+            vec![WordType::Int(1), WordType::Str(String::new()), WordType::Null],
+        ]);
+    }
+
+    #[test]
+    fn string_longer_than_table_file() {
+        let path = clean_test_dir("truncated_string");
+        let mut database = load_database_from(&path, false).unwrap();
+        assert!(matches!(execute_query("note Str notes create Émile notes insert", &mut database), Ok(None)));
+        checkpoint_database(&mut database).unwrap();
+        let bytes = fs::read(format!("{path}/notes.tbl")).unwrap();
+        fs::write(format!("{path}/notes.tbl"), &bytes[..bytes.len() - 1]).unwrap();
+        assert!(matches!(load_database_from(&path, false), Err(Error::CorruptedTable {..})));
+    }
+
+    // --- File header ---
+    #[test]
+    fn headerless_table_is_migrated_by_checkpoint() {
+        // Fixture is a table file written before the header was introduced
+        let path = clean_test_dir("table_header");
+        for file in ["stuff.tbls", "stuff.tbl"] {
+            fs::copy(format!("./src/tests_input/legacy/{file}"), format!("{path}/{file}")).unwrap();
+        }
+
+        let mut loaded = load_database_from(&path, false).unwrap();
+        let expected = [(0, "John", 19), (1, "Alexey", 29), (2, "Dmitriy", 25), (3, "Edward", 22), (4, "Emily", 19)]
+            .map(|(id, name, age)| vec![WordType::Int(id), text(name), WordType::Int(age)]);
+        assert!(rows(&loaded, 0) == expected);
+
+        // Opening keeps the file, the checkpoint writes it with the header
+        let legacy = fs::read("./src/tests_input/legacy/stuff.tbl").unwrap();
+        assert!(fs::read(format!("{path}/stuff.tbl")).unwrap() == legacy);
+        checkpoint_database(&mut loaded).unwrap();
+        let bytes = fs::read(format!("{path}/stuff.tbl")).unwrap();
+        assert!(bytes[..8] == *b"\x89RDB\r\n\x1a\n");
+        assert!(bytes[8..12] == 1u32.to_le_bytes());
+        assert!(bytes[16..24] == 5u64.to_le_bytes());
+        assert!(bytes[24..28] == 0i32.to_le_bytes());
+        assert!(rows(&load_database_from(&path, false).unwrap(), 0) == expected);
+
+        let mut newer = bytes.clone();
+        newer[8] = 2;
+        fs::write(format!("{path}/stuff.tbl"), newer).unwrap();
+        assert!(matches!(load_database_from(&path, false), Err(Error::UnsupportedVersion {version: 2, ..})));
+
+        fs::write(format!("{path}/stuff.tbl"), &bytes).unwrap();
+        fs::write(format!("{path}/stuff.tbls"), "stuff\nid:Int\nname:Str\n").unwrap();
+        assert!(matches!(load_database_from(&path, false), Err(Error::CorruptedTable {..})));
+
+        // Legacy rows have a fixed size
+        fs::write(format!("{path}/stuff.tbl"), &legacy[..100]).unwrap();
+        fs::write(format!("{path}/stuff.tbls"), "stuff\nid:Int\nname:Str\nage:Int\n").unwrap();
+        assert!(matches!(load_database_from(&path, false), Err(Error::CorruptedTable {..})));
+    }
+
+    // --- Column types ---
+    fn events(database: &mut Database) {
+        assert!(matches!(
+            execute_query("id Int active Bool score Float? views BigInt seen Timestamp key Bytes events create", database),
+            Ok(None),
+        ));
+        assert!(matches!(execute_query("0 true 2.5 5000000000L 2024-02-29T23:59:59 0x00ff events insert", database), Ok(None)));
+        assert!(matches!(execute_query("1 false -0.5 7L 1969-12-31 0x events insert", database), Ok(None)));
+        assert!(matches!(execute_query("2 true null 1L 2024-03-01 0xCAFE events insert", database), Ok(None)));
+    }
+
+    #[test]
+    fn literals_of_column_types() {
+        let mut database = memory_database();
+        events(&mut database);
+        let table = execute_query("seen 2024-03-01 < active true == and * events select", &mut database).unwrap().unwrap();
+        assert!(table.len() == 2);
+        assert!(table.get_bool(0, "active") == Some(true));
+        assert!(table.get_float(0, "score") == Some(2.5));
+        assert!(table.get_bigint(0, "views") == Some(5_000_000_000));
+        assert!(table.get_timestamp(0, "seen") == Some(1_709_251_199));
+        assert!(table.get_bytes(1, "key") == Some(&[0xca, 0xfe][..]));
+    }
+
+    #[test]
+    fn int_literal_in_float_column() {
+        let mut database = memory_database();
+        events(&mut database);
+        assert!(matches!(
+            execute_query("3 true 1 1L 2024-03-01 0x events insert", &mut database),
+            Err(Error::TypeMismatch {expected: DataType::Float, found: DataType::Int, pos: 7, ..}),
+        ));
+    }
+
+    #[test]
+    fn order_by_timestamp() {
+        let mut database = memory_database();
+        events(&mut database);
+        let table = execute_query("id seen events select seen order-by", &mut database).unwrap().unwrap();
+        assert!(table.get_timestamp(0, "seen") == Some(-86_400) && table.get_int(0, "id") == Some(1));
+    }
+
+    #[test]
+    fn aggregates_of_column_types() {
+        let mut database = memory_database();
+        events(&mut database);
+        let table = execute_query("score views events select score sum views sum views avg", &mut database).unwrap().unwrap();
+        assert!(table.get_float(0, "sum-score") == Some(2.0));
+        assert!(table.get_bigint(0, "sum-views") == Some(5_000_000_008));
+        assert!(table.get_float(0, "avg-views") == Some(5_000_000_008.0 / 3.0));
+        assert!(matches!(
+            execute_query("key events select key sum", &mut database),
+            Err(Error::NotNumeric {found: DataType::Bytes, ..}),
+        ));
+    }
+
+    #[test]
+    fn display_column_types() {
+        let mut database = memory_database();
+        events(&mut database);
+        let table = execute_query("* events select", &mut database).unwrap().unwrap();
+        assert!(table.to_string().lines().nth(1).unwrap().ends_with("2.5          5000000000 2024-02-29T23:59:59              0x00ff"));
+    }
+
+    #[test]
+    fn save_and_load_column_types() {
+        let path = clean_test_dir("column_types");
+        let mut database = load_database_from(&path, false).unwrap();
+        events(&mut database);
+        let expected = execute_query("* events select", &mut database).unwrap().unwrap();
+
+        let mut loaded = load_database_from(&path, false).unwrap();
+        assert!(rows(&loaded, 0) == expected.rows);
+        checkpoint_database(&mut loaded).unwrap();
+        let loaded = load_database_from(&path, false).unwrap();
+        assert!(rows(&loaded, 0) == expected.rows);
+        assert!(fs::read_to_string(format!("{path}/events.tbls")).unwrap()
+            == "events\nid:Int\nactive:Bool\nscore:Float?\nviews:BigInt\nseen:Timestamp\nkey:Bytes\n");
+    }
+
+    #[test]
+    fn type_names_outside_schemas() {
+        let mut database = memory_database();
+
+        assert!(matches!(execute_query("Float Str kind Str+default(Bool) things create", &mut database), Ok(None)));
+        assert!(database.tables[0].schema.cols[0].name == "Float");
+        assert!(matches!(execute_query("Bytes Bool things insert", &mut database), Ok(None)));
+        assert!(matches!(execute_query("Float Int things insert-named", &mut database), Ok(None)));
+        let table = execute_query("kind Bool == Float things select", &mut database).unwrap().unwrap();
+        assert!(table.get_str(0, "Float") == Some("Bytes") && table.get_str(1, "Float") == Some("Int"));
+    }
+
+    #[test]
+    fn quoted_default_values() {
+        let mut database = memory_database();
+
+        assert!(matches!(execute_query("id Int note Str+default(\"a+b)\") notes create", &mut database), Ok(None)));
+        assert!(matches!(execute_query("id 1 notes insert-named", &mut database), Ok(None)));
+        let table = execute_query("note notes select", &mut database).unwrap().unwrap();
+        assert!(table.get_str(0, "note") == Some("a+b)"));
+
+        let content = encode_schema(&database.tables[0].schema);
+        assert!(content == "notes\nid:Int\nnote:Str+default(\"a+b)\")\n");
+        assert!(parse_schema(&content, "notes.tbls").unwrap() == database.tables[0].schema);
+        assert!(try_parse_col_type("Str+default(\"a\"b)+unique").is_none());
+    }
+
+    // --- Single-file database ---
+    /// Exports a directory database with the `clients` and `orders` tables into `database.rdb` in `path`
+    fn single_file(path: &str) -> String {
+        let dir_path = format!("{path}/directory");
+        let file_path = format!("{path}/database.rdb");
+        fs::create_dir(&dir_path).unwrap();
+        let mut connection = Connection::open(&dir_path).unwrap();
+        let long = "x".repeat(single_file::PAGE_SIZE * 2);
+        assert!(connection.execute("id Int name Str clients create id Int orders create").unwrap().is_none());
+        assert!(connection.execute(&format!("0 John clients insert 1 {long} clients insert 7 orders insert")).unwrap().is_none());
+        connection.export(&file_path, Format::SingleFile).unwrap();
+        connection.close().unwrap();
+        file_path
+    }
+
+    #[test]
+    fn open_single_file_database() {
+        let file_path = single_file(&clean_test_dir("single_file"));
+        let mut connection = Connection::open(&file_path).unwrap();
+        assert!(connection.format() == Format::SingleFile);
+        let table = connection.execute("name clients select").unwrap().unwrap();
+        assert!(table.len() == 2 && table.get_str(1, "name") == Some("x".repeat(single_file::PAGE_SIZE * 2).as_str()));
+        assert!(connection.execute("id orders select").unwrap().unwrap().get_int(0, "id") == Some(7));
+    }
+
+    #[test]
+    fn single_file_log_replay_and_checkpoint() {
+        let file_path = single_file(&clean_test_dir("single_file_log"));
+        let mut connection = Connection::open(&file_path).unwrap();
+        assert!(connection.execute("id 0 == clients delete 2 Kate clients insert").unwrap().is_none());
+        drop(connection);
+
+        let database = load_database_from(&file_path, false).unwrap();
+        assert!(Path::new(&format!("{file_path}-wal")).exists());
+        let clients = table_idx_by_name(&database, "clients").unwrap();
+        let expected = read_table(&database, clients).unwrap().rows.clone();
+        assert!(expected.len() == 2 && expected[1][1] == text("Kate"));
+        prepare_file_checkpoint(&file_path, &database).unwrap();
+        let database = load_database_from(&file_path, false).unwrap();
+        assert!(wal::size(&format!("{file_path}-wal")) == 0);
+        assert!(read_table(&database, clients).unwrap().rows == expected);
+    }
+
+    #[test]
+    fn import_single_file_into_directory() {
+        let path = clean_test_dir("single_file_import");
+        let file_path = single_file(&path);
+        let connection = Connection::open(&file_path).unwrap();
+        connection.export(&format!("{path}/imported"), Format::Directory).unwrap();
+        let database = load_database_from(&file_path, false).unwrap();
+        assert_same_tables(&database, &load_database_from(&format!("{path}/imported"), false).unwrap());
+    }
+
+    #[test]
+    fn corrupted_single_file() {
+        let file_path = single_file(&clean_test_dir("single_file_corrupted"));
+        let mut bytes = fs::read(&file_path).unwrap();
+        bytes[20] = 200;
+        fs::write(&file_path, bytes).unwrap();
+        assert!(matches!(Connection::open(&file_path), Err(Error::CorruptedFile {..})));
+    }
+
+    /// Exports a table with twice the pool size of pages into a single file and a directory in `path`
+    fn paged_databases(path: &str) -> [String; 2] {
+        let file_path = format!("{path}/database.rdb");
+        let dir_path = format!("{path}/directory");
+        let mut connection = Connection::open_in_memory();
+        let mut query = "id Int note Str big create begin ".to_string();
+        for id in 0..pager::POOL_PAGES * 2 {
+            query.push_str(&format!("{id} {} big insert ", "x".repeat(3000)));
+        }
+        query.push_str("commit");
+        assert!(connection.execute(&query).unwrap().is_none());
+        connection.export(&file_path, Format::SingleFile).unwrap();
+        connection.export(&dir_path, Format::Directory).unwrap();
+        [file_path, dir_path]
+    }
+
+    #[test]
+    fn limit_reads_only_needed_pages() {
+        let paths = paged_databases(&clean_test_dir("paged_limit"));
+        let size = fs::metadata(format!("{}/big.tbl", paths[1])).unwrap().len() as i64;
+        for path in paths {
+            let mut database = load_database_from(&path, false).unwrap();
+            assert!(database.tables[0].rows.is_empty());
+            let before = database.paged.pool.reads();
+            let tables = execute_query("tables", &mut database).unwrap().unwrap();
+            assert!(tables.get_bigint(0, "rows") == Some(pager::POOL_PAGES as i64 * 2) && tables.get_bigint(0, "size") == Some(size));
+            assert!(database.paged.pool.reads() == before);
+            let table = execute_query("id big select 3 limit", &mut database).unwrap().unwrap();
+            assert!(table.len() == 3 && database.paged.pool.reads() - before <= 3);
+        }
+    }
+
+    #[test]
+    fn scan_keeps_pool_size_of_pages() {
+        for path in paged_databases(&clean_test_dir("paged_scan")) {
+            let mut database = load_database_from(&path, false).unwrap();
+            let before = database.paged.pool.reads();
+            let table = execute_query("id 300 == note big select", &mut database).unwrap().unwrap();
+            assert!(table.len() == 1 && table.get_str(0, "note") == Some("x".repeat(3000).as_str()));
+            assert!(database.paged.pool.reads() - before > pager::POOL_PAGES as u64);
+            assert!(database.paged.pool.cached_pages() <= pager::POOL_PAGES);
+            assert!(database.tables[0].rows.is_empty());
+        }
+    }
+
+    #[test]
+    fn changes_load_only_changed_pages() {
+        for path in paged_databases(&clean_test_dir("paged_changes")) {
+            let mut database = load_database_from(&path, false).unwrap();
+            let loaded = |database: &Database| database.paged.tables["big"].loaded_rows();
+            let updated = execute_query("id 300 == note y big update", &mut database).unwrap().unwrap();
+            assert!(updated.get_int(0, "updated") == Some(1) && loaded(&database) <= 2);
+            assert!(execute_query("1000 z big insert", &mut database).unwrap().is_none());
+            assert!(loaded(&database) <= 3 && row_count(&database, 0) == pager::POOL_PAGES * 2 + 1);
+            let table = execute_query("id 299 > id 301 < and note big select", &mut database).unwrap().unwrap();
+            assert!(table.len() == 3 && table.get_str(1, "note") == Some("y"));
+        }
+    }
+
+    #[test]
+    fn checkpoint_of_paged_table() {
+        for path in paged_databases(&clean_test_dir("paged_checkpoint")) {
+            let mut database = load_database_from(&path, false).unwrap();
+            assert!(execute_query("id 5 > big delete", &mut database).unwrap().is_none());
+            assert!(execute_query("id 0 == note y big update", &mut database).unwrap().is_some());
+            checkpoint_database(&mut database).unwrap();
+            assert!(database.tables[0].rows.is_empty() && database.paged.tables["big"].loaded_rows() == 0);
+            assert!(execute_query("id big select", &mut database).unwrap().unwrap().len() == 5);
+            assert!(rows(&load_database_from(&path, false).unwrap(), 0) == rows(&database, 0));
+        }
+    }
+
+    // --- Indexes ---
+    /// Number of rows the indexes of the table find for the conditions, `None` when they need a scan
+    fn index_candidate_count(query: &str, table: &Table) -> Option<usize> {
+        let tokens = parse_query(query).unwrap();
+        let mut words = vec![];
+        let mut conditions = vec![];
+        for Token {op, pos} in tokens {
+            match op {
+                Op::PushWord {data_type, word_type} => words.push(Word {data_type, value: word_type, pos}),
+                op @ (Op::And | Op::Or) => conditions.push(RawCondition {op, operands: None, pos}),
+                op => {
+                    let value = words.pop().unwrap();
+                    let col = words.pop().unwrap();
+                    conditions.push(RawCondition {op, operands: Some((col, value)), pos});
+                },
+            }
+        }
+        index_candidates(table, &compile_conditions(&conditions, table, 0).unwrap()).map(|rows| rows.len())
+    }
+
+    fn indexed_people(database: &mut Database) {
+        let mut query = "id Int city Str? people create begin ".to_string();
+        for id in 0..100 {
+            query.push_str(&format!("{id} city{} people insert ", id % 10));
+        }
+        query.push_str("100 null people insert commit");
+        assert!(execute_query(&query, database).unwrap().is_none());
+        assert!(execute_query("by_id id people create-index by_city city people create-index", database).unwrap().is_none());
+    }
+
+    #[test]
+    fn create_and_drop_index_errors() {
+        let mut database = memory_database();
+        indexed_people(&mut database);
+        assert!(matches!(
+            execute_query("by_id city people create-index", &mut database),
+            Err(Error::IndexExists {pos: 0, ..}),
+        ));
+        assert!(matches!(execute_query("by_age age people create-index", &mut database), Err(Error::UnknownColumn {pos: 7, ..})));
+        assert!(matches!(execute_query("by_age people drop-index", &mut database), Err(Error::UnknownIndex {pos: 0, ..})));
+    }
+
+    #[test]
+    fn index_lookups_and_ranges() {
+        let mut database = memory_database();
+        indexed_people(&mut database);
+        let table = &database.tables[0];
+        assert!(index_candidate_count("city city3 ==", table) == Some(10));
+        assert!(index_candidate_count("id 10 < id 95 > or", table) == Some(17));
+        assert!(index_candidate_count("id 20 > city city3 == and", table) == Some(8));
+        assert!(index_candidate_count("id 10 < city city3 != or", table).is_none());
+        let table = execute_query("city city3 == id 50 > and id people select", &mut database).unwrap().unwrap();
+        assert!(table.len() == 5 && table.get_int(0, "id") == Some(53));
+        let table = execute_query("id 10 < city city3 != and id people select", &mut database).unwrap().unwrap();
+        assert!(table.len() == 10);
+    }
+
+    #[test]
+    fn indexes_follow_changes() {
+        let mut database = memory_database();
+        indexed_people(&mut database);
+        assert!(execute_query("id 90 > people delete", &mut database).unwrap().is_none());
+        assert!(execute_query("city city0 == city city9 people update", &mut database).unwrap().is_some());
+        let table = execute_query("city city9 == id people select", &mut database).unwrap().unwrap();
+        assert!(table.len() == 18 && table.get_int(17, "id") == Some(89));
+    }
+
+    #[test]
+    fn rollback_restores_indexes() {
+        let mut database = memory_database();
+        indexed_people(&mut database);
+        assert!(execute_query("begin 5 city1 people insert id 40 < people delete by_id people drop-index", &mut database).unwrap().is_none());
+        assert!(database.tables[0].schema.indexes.len() == 1);
+        assert!(execute_query("rollback", &mut database).unwrap().is_none());
+        let table = execute_query("id 5 == city people select", &mut database).unwrap().unwrap();
+        assert!(table.len() == 1 && table.get_str(0, "city") == Some("city5"));
+        assert!(database.tables[0].schema.indexes.len() == 2);
+    }
+
+    #[test]
+    fn save_and_load_indexes() {
+        let path = clean_test_dir("indexes");
+        let mut database = load_database_from(&path, false).unwrap();
+        indexed_people(&mut database);
+
+        let mut loaded = load_database_from(&path, false).unwrap();
+        assert!(loaded.tables[0].schema == database.tables[0].schema);
+        checkpoint_database(&mut loaded).unwrap();
+        assert!(fs::read_to_string(format!("{path}/people.tbls")).unwrap()
+            == "people\nid:Int\ncity:Str?\nindex by_id id\nindex by_city city\n");
+        let mut loaded = load_database_from(&path, false).unwrap();
+        assert!(index_candidate_count("id 3 ==", &loaded.tables[0]) == Some(1));
+        let table = execute_query("city city9 == id people select", &mut loaded).unwrap().unwrap();
+        assert!(table.len() == 10);
+    }
+
+    #[test]
+    fn index_column_types() {
+        let mut database = memory_database();
+
+        let query = "id Int name Str? score Float seen Timestamp active Bool key Bytes people create";
+        assert!(matches!(execute_query(query, &mut database), Ok(None)));
+        assert!(matches!(execute_query("by_id id people create-index by_name name people create-index", &mut database), Ok(None)));
+        for (col, data_type) in [("score", DataType::Float), ("seen", DataType::Timestamp), ("active", DataType::Bool), ("key", DataType::Bytes)] {
+            let result = execute_query(&format!("by_{col} {col} people create-index"), &mut database);
+            assert!(matches!(result, Err(Error::NotIndexable {found, ..}) if found == data_type));
+        }
+        assert!(database.tables[0].schema.indexes.len() == 2);
+        let result = parse_schema("people\nid:Int\nscore:Float\nindex by_score score\n", "people.tbls");
+        assert!(matches!(result, Err(Error::SchemaParse {line: 4, kind: SchemaParseError::NotIndexableColumn(..), ..})));
+    }
+
+    /// Exports a table with an index into a single file in `path`
+    fn paged_people(path: &str) -> String {
+        let file_path = format!("{path}/database.rdb");
+        let mut connection = Connection::open_in_memory();
+        let mut query = "id Int city Str people create by_id id people create-index begin ".to_string();
+        for id in 0..50 {
+            query.push_str(&format!("{id} city{} people insert ", id % 5));
+        }
+        query.push_str("commit");
+        assert!(connection.execute(&query).unwrap().is_none());
+        connection.export(&file_path, Format::SingleFile).unwrap();
+        file_path
+    }
+
+    #[test]
+    fn indexes_of_paged_table_are_built_on_open() {
+        let file_path = paged_people(&clean_test_dir("paged_indexes"));
+        let mut database = load_database_from(&file_path, false).unwrap();
+        assert!(database.tables[0].rows.is_empty());
+        assert!(index_candidate_count("id 7 ==", &database.tables[0]) == Some(1));
+        let table = execute_query("id 40 > id 42 < and city people select", &mut database).unwrap().unwrap();
+        assert!(table.len() == 3 && table.get_str(0, "city") == Some("city0"));
+    }
+
+    #[test]
+    fn create_index_of_paged_table() {
+        let file_path = paged_people(&clean_test_dir("paged_create_index"));
+        let mut database = load_database_from(&file_path, false).unwrap();
+        assert!(execute_query("by_city city people create-index", &mut database).unwrap().is_none());
+        assert!(database.tables[0].rows.is_empty());
+        assert!(index_candidate_count("city city3 ==", &database.tables[0]) == Some(10));
+        assert!(execute_query("city city3 == id people select", &mut database).unwrap().unwrap().len() == 10);
+
+        let mut loaded = load_database_from(&file_path, false).unwrap();
+        assert!(index_candidate_count("city city3 ==", &loaded.tables[0]) == Some(10));
+        checkpoint_database(&mut loaded).unwrap();
+        assert!(index_candidate_count("id 7 ==", &loaded.tables[0]) == Some(1));
+        let loaded = load_database_from(&file_path, false).unwrap();
+        assert!(index_candidate_count("city city3 == id 20 < and", &loaded.tables[0]) == Some(4));
+    }
+
+    // --- Constraints ---
+    fn unique_people(database: &mut Database) {
+        assert!(execute_query("id Int+primary email Str?+unique name Str people create", database).unwrap().is_none());
+        assert!(execute_query("0 a@b.c Emily people insert 1 null John people insert 2 null Kate people insert", database).unwrap().is_none());
+    }
+
+    #[test]
+    fn multiple_primary_keys() {
+        let mut database = memory_database();
+        assert!(matches!(
+            execute_query("a Int+primary b Int+primary pairs create", &mut database),
+            Err(Error::MultiplePrimaryKeys {pos: 34, ..}),
+        ));
+    }
+
+    #[test]
+    fn insert_duplicate_value() {
+        let mut database = memory_database();
+        unique_people(&mut database);
+        match execute_query("3 d@e.f Anna people insert 0 x@y.z Emily people insert", &mut database) {
+            Err(Error::DuplicateValue {column, value: WordType::Int(0), pos: 27, ..}) => assert!(column == "id"),
+            other => panic!("expected duplicate value error, found {other:?}"),
+        }
+        assert!(execute_query("id people select", &mut database).unwrap().unwrap().len() == 3);
+        assert!(matches!(
+            execute_query("3 a@b.c Anna people insert", &mut database),
+            Err(Error::DuplicateValue {value: WordType::Str(_), ..}),
+        ));
+    }
+
+    #[test]
+    fn update_to_duplicate_value() {
+        let mut database = memory_database();
+        unique_people(&mut database);
+        assert!(matches!(
+            execute_query("id 1 == id 2 people update", &mut database),
+            Err(Error::DuplicateValue {pos: 11, ..}),
+        ));
+        assert!(matches!(
+            execute_query("id 0 > email k@l.m people update", &mut database),
+            Err(Error::DuplicateValue {..}),
+        ));
+        let updated = execute_query("id 0 == id 0 email a@b.c people update", &mut database).unwrap().unwrap();
+        assert!(updated.get_int(0, "updated") == Some(1));
+    }
+
+    #[test]
+    fn duplicate_value_of_indexed_column() {
+        let mut database = memory_database();
+        unique_people(&mut database);
+        assert!(execute_query("by_id id people create-index 5 null Anna people insert", &mut database).unwrap().is_none());
+        assert!(matches!(execute_query("5 null Anna people insert", &mut database), Err(Error::DuplicateValue {..})));
+    }
+
+    #[test]
+    fn save_and_load_constraints() {
+        let path = clean_test_dir("constraints");
+        let mut database = load_database_from(&path, false).unwrap();
+        unique_people(&mut database);
+
+        let mut loaded = load_database_from(&path, false).unwrap();
+        assert!(loaded.tables[0].schema == database.tables[0].schema);
+        assert!(loaded.tables[0].schema.cols[0].constraint() == Some(Constraint::PrimaryKey));
+        checkpoint_database(&mut loaded).unwrap();
+        assert!(fs::read_to_string(format!("{path}/people.tbls")).unwrap() == "people\nid:Int+primary\nemail:Str?+unique\nname:Str\n");
+        let mut loaded = load_database_from(&path, false).unwrap();
+        assert!(matches!(execute_query("1 n@o.p John people insert", &mut loaded), Err(Error::DuplicateValue {..})));
+    }
+
+    /// `pets` restrict deletes of their owners and cascade from their parents, `cars` cascade from owners
+    fn people_with_pets(database: &mut Database) {
+        let query = "id Int+primary name Str people create \
+            id Int+primary owner Int?+references(people.id) parent Int?+references(pets.id,cascade) pets create \
+            id Int owner Int+references(people.id,cascade) cars create";
+        assert!(execute_query(query, database).unwrap().is_none());
+        let query = "0 Emily people insert 1 John people insert \
+            0 0 null pets insert 1 null 0 pets insert 2 1 1 pets insert 3 1 null pets insert 0 0 cars insert 1 1 cars insert";
+        assert!(execute_query(query, database).unwrap().is_none());
+    }
+
+    #[test]
+    fn invalid_foreign_keys() {
+        let mut database = memory_database();
+        assert!(execute_query("id Int+primary name Str people create", &mut database).unwrap().is_none());
+        assert!(matches!(
+            execute_query("id Int owner Int+references(people.name) pets create", &mut database),
+            Err(Error::InvalidForeignKey {pos: 13, ..}),
+        ));
+        assert!(matches!(
+            execute_query("id Int owner Int+references(owners.id) pets create", &mut database),
+            Err(Error::InvalidForeignKey {reason: "parent table doesn't exist", ..}),
+        ));
+    }
+
+    #[test]
+    fn missing_parent() {
+        let mut database = memory_database();
+        people_with_pets(&mut database);
+        assert!(matches!(
+            execute_query("4 2 null pets insert", &mut database),
+            Err(Error::MissingParent {value: WordType::Int(2), pos: 2, ..}),
+        ));
+        assert!(matches!(execute_query("id 3 == owner 7 pets update", &mut database), Err(Error::MissingParent {..})));
+    }
+
+    #[test]
+    fn restricting_foreign_key() {
+        let mut database = memory_database();
+        people_with_pets(&mut database);
+        assert!(matches!(
+            execute_query("id 0 == people delete", &mut database),
+            Err(Error::RowReferenced {value: WordType::Int(0), ..}),
+        ));
+        assert!(matches!(execute_query("id 1 == id 5 people update", &mut database), Err(Error::RowReferenced {..})));
+        assert!(execute_query("* people select", &mut database).unwrap().unwrap().len() == 2);
+    }
+
+    #[test]
+    fn cascading_foreign_key() {
+        let mut database = memory_database();
+        people_with_pets(&mut database);
+        assert!(execute_query("id 0 == pets delete", &mut database).unwrap().is_none());
+        let table = execute_query("id pets select", &mut database).unwrap().unwrap();
+        assert!(table.len() == 1 && table.get_int(0, "id") == Some(3));
+        assert!(execute_query("id 3 == owner 0 pets update", &mut database).unwrap().unwrap().get_int(0, "updated") == Some(1));
+        assert!(execute_query("id 1 == people delete", &mut database).unwrap().is_none());
+        assert!(execute_query("id cars select", &mut database).unwrap().unwrap().len() == 1);
+    }
+
+    #[test]
+    fn drop_referenced_table() {
+        let mut database = memory_database();
+        people_with_pets(&mut database);
+        assert!(matches!(execute_query("people drop", &mut database), Err(Error::TableReferenced {pos: 7, ..})));
+        assert!(execute_query("pets drop people drop", &mut database).unwrap().is_none());
+        assert!(execute_query("id cars select", &mut database).unwrap().unwrap().is_empty());
+        assert!(database.tables[0].schema.cols[1].foreign_key().is_none());
+        assert!(execute_query("2 5 cars insert", &mut database).unwrap().is_none());
+    }
+
+    #[test]
+    fn save_and_load_foreign_keys() {
+        let path = clean_test_dir("foreign_keys");
+        let mut database = load_database_from(&path, false).unwrap();
+        assert!(execute_query("id Int+primary pk create ref Int+references(pk.id,cascade) fk create", &mut database).unwrap().is_none());
+
+        let mut loaded = load_database_from(&path, false).unwrap();
+        assert!(loaded.tables.iter().map(|table| &table.schema).eq(database.tables.iter().map(|table| &table.schema)));
+        checkpoint_database(&mut loaded).unwrap();
+        assert!(fs::read_to_string(format!("{path}/fk.tbls")).unwrap() == "fk\nref:Int+references(pk.id,cascade)\n");
+        let mut loaded = load_database_from(&path, false).unwrap();
+        assert!(matches!(execute_query("1 fk insert", &mut loaded), Err(Error::MissingParent {..})));
+    }
+
+    fn people_with_defaults(database: &mut Database) {
+        let query = "id Int+primary+auto name Str city Str(10)+default(Paris) age Int?+default(18) note Str? people create";
+        assert!(execute_query(query, database).unwrap().is_none());
+        assert!(execute_query("name Emily people insert-named", database).unwrap().is_none());
+        assert!(execute_query("name John age null note hi people insert-named", database).unwrap().is_none());
+        assert!(execute_query("null Kate Rome 30 null people insert", database).unwrap().is_none());
+    }
+
+    #[test]
+    fn multiple_auto_increments() {
+        let mut database = memory_database();
+        assert!(matches!(
+            execute_query("a Int+auto b Int+auto pairs create", &mut database),
+            Err(Error::MultipleAutoIncrements {pos: 28, ..}),
+        ));
+    }
+
+    #[test]
+    fn insert_with_auto_increment_and_defaults() {
+        let mut database = memory_database();
+        people_with_defaults(&mut database);
+        let table = execute_query("* people select", &mut database).unwrap().unwrap();
+        assert!(table.len() == 3);
+        assert!(table.get_int(0, "id") == Some(1) && table.get_str(0, "city") == Some("Paris") && table.get_int(0, "age") == Some(18));
+        assert!(table.get_int(1, "id") == Some(2) && table.get_int(1, "age").is_none() && table.get_str(1, "note") == Some("hi"));
+        assert!(table.get_int(2, "id") == Some(3) && table.get_str(2, "city") == Some("Rome"));
+    }
+
+    #[test]
+    fn insert_named_errors() {
+        let mut database = memory_database();
+        people_with_defaults(&mut database);
+        assert!(matches!(
+            execute_query("age 20 people insert-named", &mut database),
+            Err(Error::MissingValue {pos: 14, ..}),
+        ));
+        assert!(matches!(execute_query("people insert-named", &mut database), Err(Error::MissingColumns {pos: 7, ..})));
+        assert!(matches!(execute_query("name Anna name Mark people insert-named", &mut database), Err(Error::ColumnExists {pos: 0, ..})));
+    }
+
+    #[test]
+    fn explicit_values_move_the_sequence() {
+        let mut database = memory_database();
+        people_with_defaults(&mut database);
+        assert!(execute_query("id 10 name Anna people insert-named", &mut database).unwrap().is_none());
+        assert!(execute_query("id 10 == people delete name Mark people insert-named", &mut database).unwrap().is_none());
+        let table = execute_query("name Mark == id people select", &mut database).unwrap().unwrap();
+        assert!(table.get_int(0, "id") == Some(11));
+        execute_query("name Mark == id 20 people update", &mut database).unwrap();
+        assert!(execute_query("name Olga people insert-named", &mut database).unwrap().is_none());
+        let table = execute_query("name Olga == id people select", &mut database).unwrap().unwrap();
+        assert!(table.get_int(0, "id") == Some(21));
+    }
+
+    #[test]
+    fn save_and_load_sequence() {
+        let path = clean_test_dir("auto_increment");
+        let mut database = load_database_from(&path, false).unwrap();
+        people_with_defaults(&mut database);
+
+        let mut loaded = load_database_from(&path, false).unwrap();
+        assert!(loaded.tables[0].schema == database.tables[0].schema);
+        checkpoint_database(&mut loaded).unwrap();
+        assert!(fs::read_to_string(format!("{path}/people.tbls")).unwrap() == "people\nid:Int+primary+auto\nname:Str\n\
+            city:Str(10)+default(Paris)\nage:Int?+default(18)\nnote:Str?\nsequence 4\n");
+        let mut loaded = load_database_from(&path, false).unwrap();
+        assert!(execute_query("id 3 == people delete name Nina people insert-named", &mut loaded).unwrap().is_none());
+        let table = execute_query("name Nina == id people select", &mut loaded).unwrap().unwrap();
+        assert!(table.get_int(0, "id") == Some(4));
+    }
+
+    #[test]
+    fn sequence_overflow_in_add_column() {
+        let mut database = memory_database();
+        assert!(execute_query("x Int nums create 1 nums insert 2 nums insert", &mut database).unwrap().is_none());
+        database.tables[0].schema.sequence = i32::MAX as i64;
+        assert!(matches!(execute_query("n Int+auto nums add-column", &mut database), Err(Error::IntegerOverflow {op: "add-column", pos: 16})));
+        assert!(database.tables[0].schema.cols.len() == 1);
+    }
+
+    // --- Schema changes ---
+    fn people_and_pets(database: &mut Database) {
+        let query = "id Int+primary name Str people create owner Int+references(people.id) pets create \
+            0 Emily people insert 1 John people insert 0 pets insert";
+        assert!(execute_query(query, database).unwrap().is_none());
+    }
+
+    #[test]
+    fn add_column_to_existing_rows() {
+        let mut database = memory_database();
+        people_and_pets(&mut database);
+        assert!(execute_query("city Str+default(Paris) people add-column age Int? people add-column", &mut database).unwrap().is_none());
+        assert!(execute_query("num Int+auto people add-column", &mut database).unwrap().is_none());
+        assert!(execute_query("id 2 name Kate people insert-named", &mut database).unwrap().is_none());
+        let table = execute_query("* people select", &mut database).unwrap().unwrap();
+        assert!(table.get_str(0, "city") == Some("Paris") && table.get_int(0, "age").is_none() && table.get_int(0, "num") == Some(1));
+        assert!(table.get_int(1, "num") == Some(2) && table.get_int(2, "num") == Some(3) && table.get_int(2, "id") == Some(2));
+    }
+
+    #[test]
+    fn add_column_errors() {
+        let mut database = memory_database();
+        people_and_pets(&mut database);
+        assert!(matches!(execute_query("score Int people add-column", &mut database), Err(Error::MissingValue {pos: 17, ..})));
+        assert!(matches!(execute_query("code Int+unique+default(5) people add-column", &mut database), Err(Error::DuplicateValue {..})));
+        assert!(matches!(execute_query("name Str? people add-column", &mut database), Err(Error::ColumnExists {pos: 0, ..})));
+    }
+
+    #[test]
+    fn drop_column() {
+        let mut database = memory_database();
+        people_and_pets(&mut database);
+        assert!(execute_query("num Int+auto people add-column by_num num people create-index", &mut database).unwrap().is_none());
+        assert!(execute_query("name people drop-column", &mut database).unwrap().is_none());
+        assert!(execute_query("num 2 == id people select", &mut database).unwrap().unwrap().get_int(0, "id") == Some(1));
+        assert!(matches!(execute_query("id people drop-column", &mut database), Err(Error::ColumnReferenced {pos: 10, ..})));
+    }
+
+    #[test]
+    fn rename_column() {
+        let mut database = memory_database();
+        people_and_pets(&mut database);
+        assert!(execute_query("id key people rename-column", &mut database).unwrap().is_none());
+        assert!(database.tables[1].schema.cols[0].foreign_key().unwrap().col() == "key");
+        assert!(matches!(execute_query("5 pets insert", &mut database), Err(Error::MissingParent {..})));
+        assert!(matches!(execute_query("key name people rename-column", &mut database), Err(Error::ColumnExists {pos: 4, ..})));
+    }
+
+    #[test]
+    fn rollback_restores_columns() {
+        let mut database = memory_database();
+        people_and_pets(&mut database);
+        let query = "begin name people drop-column id key people rename-column note Str+default(x) people add-column";
+        assert!(execute_query(query, &mut database).unwrap().is_none());
+        assert!(execute_query("rollback", &mut database).unwrap().is_none());
+        let restored = execute_query("* people select", &mut database).unwrap().unwrap();
+        assert!(restored.schema.cols.iter().map(Col::name).eq(["id", "name"]));
+        assert!(restored.get_str(1, "name") == Some("John"));
+    }
+
+    #[test]
+    fn save_and_load_schema_changes() {
+        let path = clean_test_dir("alter_columns");
+        let mut database = load_database_from(&path, false).unwrap();
+        people_and_pets(&mut database);
+        let query = "age Int? people add-column name people drop-column id key people rename-column";
+        assert!(execute_query(query, &mut database).unwrap().is_none());
+
+        let mut loaded = load_database_from(&path, false).unwrap();
+        assert!(loaded.tables.iter().map(|table| &table.schema).eq(database.tables.iter().map(|table| &table.schema)));
+        assert!(rows(&loaded, 0) == rows(&database, 0));
+        checkpoint_database(&mut loaded).unwrap();
+        assert!(fs::read_to_string(format!("{path}/pets.tbls")).unwrap() == "pets\nowner:Int+references(people.key)\n");
+        let loaded = load_database_from(&path, false).unwrap();
+        assert!(rows(&loaded, table_idx_by_name(&loaded, "people").unwrap()) == rows(&database, 0));
+    }
+
+    fn people_with_ages(database: &mut Database) {
+        let query = "id Int+primary name Str age Int people create owner Int+references(people.id) pets create \
+            0 Emily 30 people insert 1 John 17 people insert 0 pets insert";
+        assert!(execute_query(query, database).unwrap().is_none());
+    }
+
+    #[test]
+    fn rename_table() {
+        let mut database = memory_database();
+        people_with_ages(&mut database);
+        assert!(execute_query("people persons rename", &mut database).unwrap().is_none());
+        assert!(matches!(execute_query("* people select", &mut database), Err(Error::UnknownTable {..})));
+        assert!(matches!(execute_query("pets persons rename", &mut database), Err(Error::TableExists {pos: 5, ..})));
+        let pets = &database.tables[table_idx_by_name(&database, "pets").unwrap()];
+        assert!(pets.schema.cols[0].foreign_key().unwrap().table() == "persons");
+        assert!(matches!(execute_query("5 pets insert", &mut database), Err(Error::MissingParent {..})));
+    }
+
+    #[test]
+    fn rollback_restores_table_name() {
+        let mut database = memory_database();
+        people_with_ages(&mut database);
+        assert!(execute_query("begin people persons rename rollback", &mut database).unwrap().is_none());
+        assert!(table_idx_by_name(&database, "people").is_some() && table_idx_by_name(&database, "persons").is_none());
+    }
+
+    #[test]
+    fn create_from_select() {
+        let mut database = memory_database();
+        people_with_ages(&mut database);
+        assert!(execute_query("age 18 > name age people select adults create", &mut database).unwrap().is_none());
+        let adults = execute_query("* adults select", &mut database).unwrap().unwrap();
+        assert!(adults.schema.cols.iter().map(Col::name).eq(["name", "age"]) && adults.rows.len() == 1);
+        assert!(adults.get_str(0, "name") == Some("Emily"));
+        assert!(execute_query("id people select ids create 0 ids insert", &mut database).unwrap().is_none());
+        assert!(matches!(execute_query("id id people select twice create", &mut database), Err(Error::ColumnExists {..})));
+    }
+
+    #[test]
+    fn create_after_update() {
+        let mut database = memory_database();
+
+        assert!(matches!(execute_query("id Int name Str clients create 0 John clients insert", &mut database), Ok(None)));
+        let result = execute_query("id 0 == name Kate clients update id Int note Str notes create", &mut database);
+        assert!(matches!(result, Ok(None)));
+        let notes = &database.tables[table_idx_by_name(&database, "notes").unwrap()];
+        assert!(notes.schema.cols.iter().map(Col::name).eq(["id", "note"]) && notes.rows.is_empty());
+    }
+
+    #[test]
+    fn save_and_load_renamed_table() {
+        let path = clean_test_dir("rename_tables");
+        let mut database = load_database_from(&path, false).unwrap();
+        people_with_ages(&mut database);
+        assert!(execute_query("people persons rename age 18 > name persons select adults create", &mut database).unwrap().is_none());
+
+        let mut loaded = load_database_from(&path, false).unwrap();
+        assert!(execute_query("name adults select", &mut loaded).unwrap().unwrap().get_str(0, "name") == Some("Emily"));
+        checkpoint_database(&mut loaded).unwrap();
+        assert!(!Path::new(&format!("{path}/people.tbl")).exists() && !Path::new(&format!("{path}/people.tbls")).exists());
+        assert!(fs::read_to_string(format!("{path}/pets.tbls")).unwrap() == "pets\nowner:Int+references(persons.id)\n");
+        let loaded = load_database_from(&path, false).unwrap();
+        let persons = table_idx_by_name(&loaded, "persons").unwrap();
+        assert!(row_count(&loaded, persons) == 2 && loaded.tables[persons].schema.cols[0].constraint() == Some(Constraint::PrimaryKey));
+    }
+
+    fn people_and_pets_to_describe(database: &mut Database) {
+        let query = "id Int+primary name Str(20)? city Str+default(Paris) people create \
+            owner Int+references(people.id,cascade) num Int+auto pets create by_name name people create-index \
+            0 Emily Rome people insert 1 John Oslo people insert 0 null pets insert";
+        assert!(execute_query(query, database).unwrap().is_none());
+    }
+
+    #[test]
+    fn tables_word() {
+        let mut database = memory_database();
+        people_and_pets_to_describe(&mut database);
+        let tables = execute_query("tables name order-by", &mut database).unwrap().unwrap();
+        assert!(tables.len() == 2 && tables.get_str(0, "name") == Some("people") && tables.get_bigint(0, "rows") == Some(2));
+        assert!(tables.get_bigint(0, "columns") == Some(3) && tables.get_bigint(0, "indexes") == Some(1) && tables.get_bigint(1, "indexes") == Some(0));
+        assert!(execute_query("tables rows 2L > having", &mut database).unwrap().unwrap().len() == 1);
+    }
+
+    #[test]
+    fn describe_word() {
+        let mut database = memory_database();
+        people_and_pets_to_describe(&mut database);
+        let people = execute_query("people describe", &mut database).unwrap().unwrap();
+        assert!(people.get_str(0, "name") == Some("id") && people.get_str(0, "constraint") == Some("primary"));
+        assert!(people.get_str(1, "type") == Some("Str(20)") && people.get_bool(1, "nullable") == Some(true));
+        assert!(people.get_str(1, "index") == Some("by_name") && people.get_str(2, "default") == Some("Paris"));
+        let pets = execute_query("pets describe", &mut database).unwrap().unwrap();
+        assert!(pets.get_str(0, "references") == Some("people.id,cascade") && pets.get_bool(1, "auto") == Some(true));
+        assert!(matches!(execute_query("dogs describe", &mut database), Err(Error::UnknownTable {pos: 0, ..})));
+    }
+
+    #[test]
+    fn sizes_of_files() {
+        let path = clean_test_dir("introspection");
+        let mut database = load_database_from(&path, false).unwrap();
+        people_and_pets_to_describe(&mut database);
+        checkpoint_database(&mut database).unwrap();
+        let file_size = |file: &str| fs::metadata(format!("{path}/{file}")).unwrap().len() as i64;
+        let tables = execute_query("tables name order-by", &mut database).unwrap().unwrap();
+        assert!(tables.get_bigint(0, "size") == Some(file_size("people.tbl")));
+        let info = execute_query("info", &mut database).unwrap().unwrap();
+        assert!(info.get_str(0, "format") == Some("directory") && info.get_bigint(0, "tables") == Some(2));
+        let total = ["people.tbl", "people.tbls", "pets.tbl", "pets.tbls"].iter().map(|file| file_size(file)).sum::<i64>();
+        assert!(info.get_bigint(0, "size") == Some(total) && info.get_bigint(0, "log") == Some(0));
+
+        assert!(execute_query("2 Anna Rome people insert", &mut database).unwrap().is_none());
+        let info = execute_query("info", &mut database).unwrap().unwrap();
+        assert!(file_size("wal.log") > 0);
+        assert!(info.get_bigint(0, "size") == Some(total) && info.get_bigint(0, "log") == Some(file_size("wal.log")));
+    }
 }
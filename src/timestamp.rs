@@ -0,0 +1,79 @@
+//! Conversion of `Timestamp` literals like `2024-05-01` or `2024-05-01T12:30:00`
+//! to seconds since 1970-01-01T00:00:00 UTC and back
+
+const SECS_PER_DAY: i64 = 24 * 60 * 60;
+
+pub fn parse(literal: &str) -> Option<i64> {
+    let (date, time) = match literal.split_once('T') {
+        Some((date, time)) => (date, Some(time)),
+        None => (literal, None),
+    };
+
+    let mut date = date.split('-');
+    let year = number(date.next()?, 4)?;
+    let month = number(date.next()?, 2)?;
+    let day = number(date.next()?, 2)?;
+    if date.next().is_some() || !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+        return None;
+    }
+
+    let mut secs = days_from_civil(year, month, day) * SECS_PER_DAY;
+    if let Some(time) = time {
+        let mut time = time.split(':');
+        let hour = number(time.next()?, 2)?;
+        let minute = number(time.next()?, 2)?;
+        let second = number(time.next()?, 2)?;
+        if time.next().is_some() || hour > 23 || minute > 59 || second > 59 {
+            return None;
+        }
+        secs += hour * 60 * 60 + minute * 60 + second;
+    }
+    Some(secs)
+}
+
+pub fn format(secs: i64) -> String {
+    let (year, month, day) = civil_from_days(secs.div_euclid(SECS_PER_DAY));
+    let time = secs.rem_euclid(SECS_PER_DAY);
+    format!("{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}", time / 3600, time / 60 % 60, time % 60)
+}
+
+/// Parses exactly `len` decimal digits
+fn number(digits: &str, len: usize) -> Option<i64> {
+    if digits.len() != len || !digits.bytes().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse().ok()
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// Conversions between days since 1970-01-01 and the proleptic Gregorian calendar,
+// counted in 400-year eras of 146097 days which start on March 1
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
@@ -0,0 +1,525 @@
+use std::io::Write;
+use std::fs::{self, OpenOptions};
+use std::path::Path;
+
+use crate::{Col, ColType, Constraint, DataType, ForeignKey, OnDelete, Error, Float, Row, TableSchema, WordType};
+use crate::error::{io_error, Warning};
+
+pub const LOG_FILE: &str = "wal.log";
+
+// Every record is framed as `<payload length: u32><crc32 of payload: u32><payload>`,
+// so a record torn by a crash in the middle of an append can be detected on replay
+const FRAME_HEADER_LEN: usize = 8;
+// Payload of the frame closing records of one transaction
+const COMMIT_PAYLOAD: [u8; 1] = [255];
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum LogRecord {
+    Insert {
+        table: String,
+        row: Row,
+    },
+    Delete {
+        table: String,
+        rows: Vec<usize>,
+    },
+    Update {
+        table: String,
+        rows: Vec<usize>,
+        values: Vec<(usize, WordType)>,
+    },
+    Create {
+        schema: TableSchema,
+    },
+    Drop {
+        table: String,
+    },
+    CreateIndex {
+        table: String,
+        name: String,
+        col: usize,
+    },
+    DropIndex {
+        table: String,
+        name: String,
+    },
+    DropForeignKey {
+        table: String,
+        col: usize,
+    },
+    AddColumn {
+        table: String,
+        col: Col,
+    },
+    DropColumn {
+        table: String,
+        col: usize,
+    },
+    RenameColumn {
+        table: String,
+        col: usize,
+        name: String,
+    },
+    RenameTable {
+        table: String,
+        name: String,
+    },
+}
+
+impl LogRecord {
+    /// Name of the table changed by the record
+    pub fn table(&self) -> &str {
+        match self {
+            LogRecord::Insert {table, ..} => table,
+            LogRecord::Delete {table, ..} => table,
+            LogRecord::Update {table, ..} => table,
+            LogRecord::Create {schema} => &schema.name,
+            LogRecord::Drop {table} => table,
+            LogRecord::CreateIndex {table, ..} => table,
+            LogRecord::DropIndex {table, ..} => table,
+            LogRecord::DropForeignKey {table, ..} => table,
+            LogRecord::AddColumn {table, ..} => table,
+            LogRecord::DropColumn {table, ..} => table,
+            LogRecord::RenameColumn {table, ..} => table,
+            LogRecord::RenameTable {table, ..} => table,
+        }
+    }
+}
+
+pub fn log_path(dir: &str) -> String {
+    format!("{dir}/{LOG_FILE}")
+}
+
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xFFFFFFFFu32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB88320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn encode_str(bytes: &mut Vec<u8>, value: &str) {
+    bytes.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(value.as_bytes());
+}
+
+fn encode_usize(bytes: &mut Vec<u8>, value: usize) {
+    bytes.extend_from_slice(&(value as u64).to_le_bytes());
+}
+
+// High bits of an encoded column type mark a nullable column and
+// a column with the maximum length, which follows the type
+const NULLABLE_FLAG: u8 = 0x80;
+const MAX_LEN_FLAG: u8 = 0x40;
+// Constraint of the column
+const PRIMARY_KEY_FLAG: u8 = 0x20;
+const UNIQUE_FLAG: u8 = 0x10;
+// Byte of flags of the column options follows the type and the maximum length,
+// the options are encoded after it in the order of their flags
+const OPTIONS_FLAG: u8 = 0x08;
+// Foreign key as `<parent table><parent column><on delete: u8>`
+const FOREIGN_KEY_OPTION: u8 = 0x01;
+const AUTO_INCREMENT_OPTION: u8 = 0x02;
+// Default value as a word
+const DEFAULT_OPTION: u8 = 0x04;
+
+fn encode_col_type(bytes: &mut Vec<u8>, col_type: &ColType) {
+    assert!(DataType::Count as u8 == 9, "Exhaustive DataType handling in encode_col_type()");
+    let mut byte = col_type.data_type as u8;
+    if col_type.nullable {
+        byte |= NULLABLE_FLAG;
+    }
+    if col_type.max_len.is_some() {
+        byte |= MAX_LEN_FLAG;
+    }
+    match col_type.constraint {
+        Some(Constraint::PrimaryKey) => byte |= PRIMARY_KEY_FLAG,
+        Some(Constraint::Unique) => byte |= UNIQUE_FLAG,
+        None => (),
+    }
+    let mut options = 0;
+    if col_type.foreign_key.is_some() {
+        options |= FOREIGN_KEY_OPTION;
+    }
+    if col_type.auto_increment {
+        options |= AUTO_INCREMENT_OPTION;
+    }
+    if col_type.default.is_some() {
+        options |= DEFAULT_OPTION;
+    }
+    if options != 0 {
+        byte |= OPTIONS_FLAG;
+    }
+    bytes.push(byte);
+    if let Some(max_len) = col_type.max_len {
+        bytes.extend_from_slice(&max_len.to_le_bytes());
+    }
+    if options != 0 {
+        bytes.push(options);
+    }
+    if let Some(foreign_key) = &col_type.foreign_key {
+        encode_str(bytes, &foreign_key.table);
+        encode_str(bytes, &foreign_key.col);
+        bytes.push(foreign_key.on_delete as u8);
+    }
+    if let Some(default) = &col_type.default {
+        encode_word(bytes, default);
+    }
+}
+
+fn encode_word(bytes: &mut Vec<u8>, word: &WordType) {
+    match word {
+        WordType::Int(value) => {
+            bytes.push(0);
+            bytes.extend_from_slice(&value.to_le_bytes());
+        },
+        WordType::Str(value) => {
+            bytes.push(1);
+            encode_str(bytes, value);
+        },
+        WordType::Type(col_type) => {
+            bytes.push(2);
+            encode_col_type(bytes, col_type);
+        },
+        WordType::Null => bytes.push(3),
+        WordType::Bool(value) => bytes.extend_from_slice(&[4, *value as u8]),
+        WordType::Float(Float(value)) => {
+            bytes.push(5);
+            bytes.extend_from_slice(&value.to_le_bytes());
+        },
+        WordType::BigInt(value) => {
+            bytes.push(6);
+            bytes.extend_from_slice(&value.to_le_bytes());
+        },
+        WordType::Timestamp(value) => {
+            bytes.push(7);
+            bytes.extend_from_slice(&value.to_le_bytes());
+        },
+        WordType::Bytes(value) => {
+            bytes.push(8);
+            bytes.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(value);
+        },
+    }
+}
+
+fn encode_record(record: &LogRecord) -> Vec<u8> {
+    let mut bytes = vec![];
+    match record {
+        LogRecord::Insert {table, row} => {
+            bytes.push(0);
+            encode_str(&mut bytes, table);
+            encode_usize(&mut bytes, row.len());
+            for word in row {
+                encode_word(&mut bytes, word);
+            }
+        },
+        LogRecord::Delete {table, rows} => {
+            bytes.push(1);
+            encode_str(&mut bytes, table);
+            encode_usize(&mut bytes, rows.len());
+            for row in rows {
+                encode_usize(&mut bytes, *row);
+            }
+        },
+        LogRecord::Update {table, rows, values} => {
+            bytes.push(2);
+            encode_str(&mut bytes, table);
+            encode_usize(&mut bytes, rows.len());
+            for row in rows {
+                encode_usize(&mut bytes, *row);
+            }
+            encode_usize(&mut bytes, values.len());
+            for (idx, value) in values {
+                encode_usize(&mut bytes, *idx);
+                encode_word(&mut bytes, value);
+            }
+        },
+        LogRecord::Create {schema} => {
+            bytes.push(3);
+            encode_str(&mut bytes, &schema.name);
+            encode_usize(&mut bytes, schema.cols.len());
+            for col in &schema.cols {
+                encode_str(&mut bytes, &col.name);
+                encode_col_type(&mut bytes, &col.col_type());
+            }
+        },
+        LogRecord::Drop {table} => {
+            bytes.push(4);
+            encode_str(&mut bytes, table);
+        },
+        LogRecord::CreateIndex {table, name, col} => {
+            bytes.push(5);
+            encode_str(&mut bytes, table);
+            encode_str(&mut bytes, name);
+            encode_usize(&mut bytes, *col);
+        },
+        LogRecord::DropIndex {table, name} => {
+            bytes.push(6);
+            encode_str(&mut bytes, table);
+            encode_str(&mut bytes, name);
+        },
+        LogRecord::DropForeignKey {table, col} => {
+            bytes.push(7);
+            encode_str(&mut bytes, table);
+            encode_usize(&mut bytes, *col);
+        },
+        LogRecord::AddColumn {table, col} => {
+            bytes.push(8);
+            encode_str(&mut bytes, table);
+            encode_str(&mut bytes, &col.name);
+            encode_col_type(&mut bytes, &col.col_type());
+        },
+        LogRecord::DropColumn {table, col} => {
+            bytes.push(9);
+            encode_str(&mut bytes, table);
+            encode_usize(&mut bytes, *col);
+        },
+        LogRecord::RenameColumn {table, col, name} => {
+            bytes.push(10);
+            encode_str(&mut bytes, table);
+            encode_usize(&mut bytes, *col);
+            encode_str(&mut bytes, name);
+        },
+        LogRecord::RenameTable {table, name} => {
+            bytes.push(11);
+            encode_str(&mut bytes, table);
+            encode_str(&mut bytes, name);
+        },
+    }
+    bytes
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.bytes.len() < len {
+            return None;
+        }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Some(head)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    fn usize(&mut self) -> Option<usize> {
+        Some(u64::from_le_bytes(self.take(8)?.try_into().unwrap()) as usize)
+    }
+
+    fn str(&mut self) -> Option<String> {
+        let len = u32::from_le_bytes(self.take(4)?.try_into().unwrap()) as usize;
+        String::from_utf8(self.take(len)?.to_vec()).ok()
+    }
+
+    fn col_type(&mut self) -> Option<ColType> {
+        let byte = self.u8()?;
+        let data_type = match byte & !(NULLABLE_FLAG | MAX_LEN_FLAG | PRIMARY_KEY_FLAG | UNIQUE_FLAG | OPTIONS_FLAG) {
+            0 => DataType::Int,
+            1 => DataType::Str,
+            2 => DataType::Type,
+            3 => DataType::Bool,
+            4 => DataType::Float,
+            5 => DataType::BigInt,
+            6 => DataType::Timestamp,
+            7 => DataType::Bytes,
+            _ => return None,
+        };
+        let max_len = match byte & MAX_LEN_FLAG {
+            0 => None,
+            _ => Some(u32::from_le_bytes(self.take(4)?.try_into().unwrap())),
+        };
+        let constraint = match (byte & PRIMARY_KEY_FLAG != 0, byte & UNIQUE_FLAG != 0) {
+            (false, false) => None,
+            (true, false) => Some(Constraint::PrimaryKey),
+            (false, true) => Some(Constraint::Unique),
+            (true, true) => return None,
+        };
+        let options = match byte & OPTIONS_FLAG {
+            0 => 0,
+            _ => self.u8()?,
+        };
+        if options & !(FOREIGN_KEY_OPTION | AUTO_INCREMENT_OPTION | DEFAULT_OPTION) != 0 {
+            return None;
+        }
+        let foreign_key = match options & FOREIGN_KEY_OPTION {
+            0 => None,
+            _ => {
+                let (table, col) = (self.str()?, self.str()?);
+                let on_delete = match self.u8()? {
+                    0 => OnDelete::Restrict,
+                    1 => OnDelete::Cascade,
+                    _ => return None,
+                };
+                Some(Box::new(ForeignKey {table, col, on_delete}))
+            },
+        };
+        let default = match options & DEFAULT_OPTION {
+            0 => None,
+            _ => Some(Box::new(self.word()?)),
+        };
+        let auto_increment = options & AUTO_INCREMENT_OPTION != 0;
+        Some(ColType {data_type, nullable: byte & NULLABLE_FLAG != 0, max_len, constraint, foreign_key, auto_increment, default})
+    }
+
+    fn word(&mut self) -> Option<WordType> {
+        match self.u8()? {
+            0 => Some(WordType::Int(i32::from_le_bytes(self.take(4)?.try_into().unwrap()))),
+            1 => Some(WordType::Str(self.str()?)),
+            2 => Some(WordType::Type(self.col_type()?)),
+            3 => Some(WordType::Null),
+            4 => match self.u8()? {
+                0 => Some(WordType::Bool(false)),
+                1 => Some(WordType::Bool(true)),
+                _ => None,
+            },
+            5 => Some(WordType::Float(Float(f64::from_le_bytes(self.take(8)?.try_into().unwrap())))),
+            6 => Some(WordType::BigInt(i64::from_le_bytes(self.take(8)?.try_into().unwrap()))),
+            7 => Some(WordType::Timestamp(i64::from_le_bytes(self.take(8)?.try_into().unwrap()))),
+            8 => {
+                let len = u32::from_le_bytes(self.take(4)?.try_into().unwrap()) as usize;
+                Some(WordType::Bytes(self.take(len)?.to_vec()))
+            },
+            _ => None,
+        }
+    }
+
+    fn idxs(&mut self) -> Option<Vec<usize>> {
+        let mut idxs = vec![];
+        for _ in 0..self.usize()? {
+            idxs.push(self.usize()?);
+        }
+        Some(idxs)
+    }
+
+    fn record(&mut self) -> Option<LogRecord> {
+        let record = match self.u8()? {
+            0 => {
+                let table = self.str()?;
+                let mut row = vec![];
+                for _ in 0..self.usize()? {
+                    row.push(self.word()?);
+                }
+                LogRecord::Insert {table, row}
+            },
+            1 => LogRecord::Delete {table: self.str()?, rows: self.idxs()?},
+            2 => {
+                let table = self.str()?;
+                let rows = self.idxs()?;
+                let mut values = vec![];
+                for _ in 0..self.usize()? {
+                    values.push((self.usize()?, self.word()?));
+                }
+                LogRecord::Update {table, rows, values}
+            },
+            3 => {
+                let name = self.str()?;
+                let mut cols = vec![];
+                for _ in 0..self.usize()? {
+                    let name = self.str()?;
+                    cols.push(Col::new(name, self.col_type()?));
+                }
+                LogRecord::Create {schema: TableSchema {name, cols, indexes: vec![], sequence: 1}}
+            },
+            4 => LogRecord::Drop {table: self.str()?},
+            5 => LogRecord::CreateIndex {table: self.str()?, name: self.str()?, col: self.usize()?},
+            6 => LogRecord::DropIndex {table: self.str()?, name: self.str()?},
+            7 => LogRecord::DropForeignKey {table: self.str()?, col: self.usize()?},
+            8 => {
+                let table = self.str()?;
+                let name = self.str()?;
+                LogRecord::AddColumn {table, col: Col::new(name, self.col_type()?)}
+            },
+            9 => LogRecord::DropColumn {table: self.str()?, col: self.usize()?},
+            10 => LogRecord::RenameColumn {table: self.str()?, col: self.usize()?, name: self.str()?},
+            11 => LogRecord::RenameTable {table: self.str()?, name: self.str()?},
+            _ => return None,
+        };
+        if !self.bytes.is_empty() {
+            return None;
+        }
+        Some(record)
+    }
+}
+
+fn push_frame(frame: &mut Vec<u8>, payload: &[u8]) {
+    frame.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    frame.extend_from_slice(&crc32(payload).to_le_bytes());
+    frame.extend_from_slice(payload);
+}
+
+/// Appends records of a transaction followed by a commit mark to the log
+/// file `path` and flushes it to disk.
+pub fn append(path: &str, records: &[LogRecord]) -> Result<(), Error> {
+    let mut frames = vec![];
+    for record in records {
+        push_frame(&mut frames, &encode_record(record));
+    }
+    push_frame(&mut frames, &COMMIT_PAYLOAD);
+
+    let mut file = OpenOptions::new().create(true).append(true).open(path).map_err(io_error("open", path))?;
+    let len = file.metadata().map_err(io_error("stat", path))?.len();
+    let written = file.write_all(&frames).map_err(io_error("write to", path))
+        .and_then(|_| file.sync_data().map_err(io_error("sync", path)));
+    // A torn transaction is cut off, so transactions appended later aren't lost behind it on replay
+    if written.is_err() {
+        let _ = file.set_len(len).and_then(|_| file.sync_data());
+    }
+    written
+}
+
+/// Reads records of all committed transactions from the log file `path`.
+/// Records after the last commit mark are cut off, so new transactions are appended after valid ones.
+pub fn read(path: &str, warnings: &mut Vec<Warning>) -> Result<Vec<LogRecord>, Error> {
+    if !Path::new(path).exists() {
+        return Ok(vec![]);
+    }
+    let bytes = fs::read(path).map_err(io_error("read", path))?;
+
+    let mut records = vec![];
+    let mut uncommitted = vec![];
+    let mut committed_len = 0;
+    let mut offset = 0;
+    while bytes.len() - offset >= FRAME_HEADER_LEN {
+        let len = u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap()) as usize;
+        let crc = u32::from_le_bytes(bytes[offset + 4..offset + 8].try_into().unwrap());
+        let start = offset + FRAME_HEADER_LEN;
+        if bytes.len() - start < len || crc32(&bytes[start..start + len]) != crc {
+            break;
+        }
+        let payload = &bytes[start..start + len];
+        offset = start + len;
+        if payload == COMMIT_PAYLOAD {
+            records.append(&mut uncommitted);
+            committed_len = offset;
+            continue;
+        }
+        match (Decoder {bytes: payload}).record() {
+            Some(record) => uncommitted.push(record),
+            None => return Err(Error::CorruptedLog {
+                detail: format!("invalid record at offset {}", start - FRAME_HEADER_LEN),
+                path: path.to_string(),
+            }),
+        }
+    }
+
+    if committed_len != bytes.len() {
+        warnings.push(Warning::IncompleteTransaction {path: path.to_string()});
+        let truncated = OpenOptions::new().write(true).open(path)
+            .and_then(|file| file.set_len(committed_len as u64).and_then(|_| file.sync_all()));
+        truncated.map_err(io_error("truncate", path))?;
+    }
+    Ok(records)
+}
+
+pub fn size(path: &str) -> u64 {
+    fs::metadata(path).map(|metadata| metadata.len()).unwrap_or(0)
+}